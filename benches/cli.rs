@@ -11,7 +11,6 @@ use escargot::CargoBuild;
 fn rmx() -> Command {
     CargoBuild::new()
         .bin("rmx")
-        .features("auto-interactive")
         .current_release()
         .run()
         .unwrap()
@@ -2,7 +2,10 @@ use std::borrow::ToOwned;
 use std::ffi::OsString;
 
 use clap::builder::PossibleValuesParser;
-use clap::{crate_authors, crate_description, crate_version, Arg, ArgMatches, Command, ValueHint};
+use clap::{
+    crate_authors, crate_description, crate_version, value_parser, Arg, ArgMatches, Command,
+    ValueHint,
+};
 
 use crate::core::BIN_NAME;
 
@@ -16,26 +19,56 @@ pub fn rm_options() -> Command<'static> {
             Arg::new("force")
                 .help("ignore nonexistent files and arguments, never prompt")
                 .short('f')
+                .multiple_occurrences(true)
         )
         .arg(
             Arg::new("interactive_always")
                 .help("prompt before every removal")
                 .short('i')
+                .multiple_occurrences(true)
         )
         .arg(
             Arg::new("interactive_once")
                 .help("prompt once before removing more than three files, or when removing recursively; less
 intrusive than -i, while still giving protection against most mistakes")
                 .short('I')
+                .multiple_occurrences(true)
         )
         .arg(
             Arg::new("interactive")
                 .help("prompt according to WHEN: never, once (-I), or always (-i); without WHEN, prompt always")
                 .long("interactive")
                 .takes_value(true)
+                .min_values(0)
+                .multiple_values(false)
+                .require_equals(true)
+                .multiple_occurrences(true)
+                .value_name("WHEN")
+                .default_missing_value("always")
                 .value_parser(PossibleValuesParser::new(vec!["never", "once", "always"]))
                 .id("WHEN")
         )
+        .arg(
+            Arg::new("assume_yes")
+                .help("answer yes to every prompt without asking, printing it along with the answer")
+                .long("assume-yes")
+                .conflicts_with("assume_no")
+        )
+        .arg(
+            Arg::new("assume_no")
+                .help("answer no to every prompt without asking, printing it along with the answer")
+                .long("assume-no")
+        )
+        .arg(
+            Arg::new("answers")
+                .help("answer prompts in order from the comma separated LIST instead of asking: y, n, a(ll), s(kip
+directory) or q(uit), and for --pick y, n or the indices to check separated by spaces; also read from
+RMX_ANSWERS")
+                .long("answers")
+                .takes_value(true)
+                .value_name("LIST")
+                .conflicts_with_all(&["assume_yes", "assume_no"])
+        )
         .arg(
             Arg::new("recursive")
                 .help("remove directories and their contents recursively")
@@ -60,10 +93,22 @@ intrusive than -i, while still giving protection against most mistakes")
 device from its parent")
                 .long("preserve-root")
                 .takes_value(true)
-                .allow_invalid_utf8(true)
-                .value_hint(ValueHint::FilePath)
+                .min_values(0)
+                .multiple_values(false)
+                .require_equals(true)
+                .value_parser(PossibleValuesParser::new(vec!["all"]))
                 .id("ALL")
         )
+        .arg(
+            Arg::new("protect")
+                .help("refuse to remove PATH, can be given multiple times")
+                .long("protect")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .allow_invalid_utf8(true)
+                .value_name("PATH")
+                .value_hint(ValueHint::AnyPath)
+        )
         .arg(
             Arg::new("verbose")
                 .help("explain what is being done")
@@ -89,6 +134,18 @@ from that of the corresponding command line argument")
         );
     }
 
+    #[cfg(target_os = "linux")]
+    {
+        command = command
+        .arg(
+            Arg::new("clear_immutable")
+                .help("clear the immutable and append-only attributes (see 'chattr') keeping an entry or its directory
+from being removed, then retry; asks first unless running as root")
+                .long("clear-immutable")
+                .conflicts_with("rip")
+        );
+    }
+
     // New features
     #[cfg(any(windows, unix))]
     {
@@ -101,23 +158,80 @@ from that of the corresponding command line argument")
         )
         .arg(
             Arg::new("follow_links")
-            .help("follow symbolic links; this does not handle cycles")
+            .help("follow symbolic links according to WHERE: only to targets inside the directory operand being
+removed (not on Windows), or any target; without WHERE, any; loops and links back to a directory being removed
+are skipped")
             .long("follow-links")
             .short('l')
+            .takes_value(true)
+            .min_values(0)
+            .multiple_values(false)
+            .require_equals(true)
+            .value_name("WHERE")
+            .value_parser(PossibleValuesParser::new(FOLLOW_LINKS))
+            .default_missing_value("any")
         )
         .arg(
             Arg::new("rip")
-            .help("multithreaded force remove, intended for removing deeply nested directories; will not respect any other flags, use with caution")
+            .help("multithreaded force remove, intended for removing deeply nested directories; never prompts and only
+respects --preserve-root, --one-file-system, --verbose, --progress, --summary and --dry-run, use with caution")
             .long("rip")
             .short('x')
             .conflicts_with_all(&["dir", "recursive", "force", "WHEN", "interactive_always", "interactive_once", "trash", "shred"])
         )
+        .arg(
+            Arg::new("threads")
+            .help("number of threads used by --rip; defaults to the available parallelism")
+            .long("threads")
+            .takes_value(true)
+            .value_name("N")
+            .value_parser(value_parser!(u16).range(1..))
+            .requires("rip")
+        )
         .arg(
             Arg::new("shred")
-            .help("wipe a file from disk and try to make it unrecoverable; similar to GNU 'shred'. folders are skipped")
+            .help("wipe a file from disk and try to make it unrecoverable; similar to GNU 'shred'. folders are skipped,
+block devices are overwritten in place after confirmation and never removed")
             .long("shred")
             // Shredding and sending to trash is nonsense
             .conflicts_with_all(&["trash", "rip"])
+        )
+        .arg(
+            Arg::new("wipe_free_space")
+            .help("fill the free space of the file system holding DIR with random data and release it afterwards, so
+previously removed files can't be recovered")
+            .long("wipe-free-space")
+            .takes_value(true)
+            .allow_invalid_utf8(true)
+            .value_hint(ValueHint::DirPath)
+            .value_name("DIR")
+            .conflicts_with_all(&["trash", "rip"])
+        )
+        .arg(
+            Arg::new("dry_run")
+            .help("don't remove anything, print what would be removed instead")
+            .long("dry-run")
+            .conflicts_with("wipe_free_space")
+        )
+        .arg(
+            Arg::new("pick")
+            .help("choose what to remove from a tree of the operands with their sizes before anything is removed;
+implies -r")
+            .long("pick")
+            .conflicts_with_all(&["rip", "wipe_free_space"])
+        )
+        .arg(
+            Arg::new("progress")
+            .help("show how many entries were removed, bytes freed, the rate and the time left on stderr while
+removing, when it's a terminal")
+            .long("progress")
+            .conflicts_with_all(&["verbose", "dry_run"])
+        )
+        .arg(
+            Arg::new("summary")
+            .help("print totals once done: files, directories and symlinks removed, bytes freed, entries skipped
+and why, errors and the time it took")
+            .long("summary")
         );
     }
 
@@ -127,19 +241,29 @@ from that of the corresponding command line argument")
 #[derive(Debug, Eq, PartialEq)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct RmOptions {
+    /// Ignore nonexistent files, unless a later `-i` or `-I` took that back
     pub force: bool,
-    pub interactive_always: bool,
-    pub interactive_once: bool,
+    /// Decided by whichever of `-f`, `-i`, `-I` and `--interactive` came last
     pub interactive: InteractiveMode,
+    /// `--assume-yes` or `--assume-no`, what every prompt is answered without asking
+    pub assume: Option<bool>,
+    /// `--answers` or `RMX_ANSWERS`, what prompts are answered with in order
+    pub answers: Option<Vec<String>>,
 
     #[cfg(unix)]
     pub one_file_system: bool,
 
+    #[cfg(target_os = "linux")]
+    pub clear_immutable: bool,
+
     #[cfg(any(windows, unix))]
     pub no_preserve_root: bool,
 
     #[cfg(any(windows, unix))]
-    pub preserve_root: OsString,
+    pub preserve_all_root: bool,
+
+    #[cfg(any(windows, unix))]
+    pub protect: Vec<OsString>,
 
     pub recursive: bool,
     pub dir: bool,
@@ -147,95 +271,192 @@ pub struct RmOptions {
     pub file: Vec<OsString>,
 
     // New features
-    pub follow_symlinks: bool,
+    pub follow_symlinks: FollowLinks,
     pub rip: bool,
+    pub threads: Option<usize>,
     pub trash: bool,
     pub shred: bool,
+    pub wipe_free_space: Option<OsString>,
+    pub dry_run: bool,
+    pub pick: bool,
+    pub progress: bool,
+    pub summary: bool,
 }
 
 impl Default for RmOptions {
     fn default() -> Self {
         Self {
             force: false,
-            interactive_always: false,
-            interactive_once: false,
-            interactive: InteractiveMode::Never,
+            interactive: InteractiveMode::WriteProtected,
+            assume: None,
+            answers: None,
             #[cfg(unix)]
             one_file_system: false,
+            #[cfg(target_os = "linux")]
+            clear_immutable: false,
             #[cfg(any(windows, unix))]
             no_preserve_root: false,
             #[cfg(any(windows, unix))]
-            preserve_root: OsString::from("all"),
+            preserve_all_root: false,
+            #[cfg(any(windows, unix))]
+            protect: Vec::new(),
             recursive: false,
             dir: false,
             verbose: false,
             file: Vec::new(),
-            follow_symlinks: false,
+            follow_symlinks: FollowLinks::Never,
             rip: false,
+            threads: None,
             trash: false,
             shred: false,
+            wipe_free_space: None,
+            dry_run: false,
+            pick: false,
+            progress: false,
+            summary: false,
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub enum InteractiveMode {
+    /// `-f` or `--interactive=never`, no prompt whatsoever
     Never,
+    /// Only for write-protected entries, without any of the flags
+    #[default]
+    WriteProtected,
+    /// `-I`, once up front before removing more than three files or recursively, then like
+    /// `WriteProtected`
     Once,
+    /// `-i`
     Always,
 }
 
-impl Default for InteractiveMode {
-    fn default() -> Self {
-        Self::Never
+/// What `--follow-links` takes. Telling whether a target is inside the operand climbs through `..`
+/// comparing inode numbers, which Windows resolves in the path instead of on disk
+#[cfg(unix)]
+const FOLLOW_LINKS: [&str; 2] = ["inside", "any"];
+#[cfg(windows)]
+const FOLLOW_LINKS: [&str; 1] = ["any"];
+
+/// Which symlink targets are removed along with the links
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum FollowLinks {
+    #[default]
+    Never,
+    /// Only targets within the directory operand
+    Inside,
+    Any,
+}
+
+impl FollowLinks {
+    #[must_use]
+    pub fn enabled(self) -> bool {
+        self != Self::Never
     }
 }
 
 impl From<&ArgMatches> for RmOptions {
     fn from(args: &ArgMatches) -> Self {
+        let (interactive, force) = interactivity(args);
+
         Self {
-            force: args.is_present("force"),
-            interactive_always: args.is_present("interactive_always"),
-            interactive_once: args.is_present("interactive_once"),
-            interactive: {
-                match args.value_of("WHEN") {
-                    Some("always") => InteractiveMode::Always,
-                    Some("once") => InteractiveMode::Once,
-                    _ => InteractiveMode::Never,
-                }
+            force,
+            interactive,
+            assume: if args.is_present("assume_yes") {
+                Some(true)
+            } else if args.is_present("assume_no") {
+                Some(false)
+            } else {
+                None
             },
+            answers: answers(args),
 
             #[cfg(unix)]
             one_file_system: args.is_present("one_file_system"),
 
+            #[cfg(target_os = "linux")]
+            clear_immutable: args.is_present("clear_immutable"),
+
+            #[cfg(any(unix, windows))]
+            preserve_all_root: args.value_of("ALL") == Some("all"),
             #[cfg(any(unix, windows))]
-            preserve_root: args
-                .value_of_os("ALL")
-                .map_or_else(|| OsString::from("all"), ToOwned::to_owned),
+            protect: args
+                .values_of_os("protect")
+                .map(|t| t.map(ToOwned::to_owned).collect())
+                .unwrap_or_default(),
             #[cfg(any(unix, windows))]
             no_preserve_root: args.is_present("no_preserve_root"),
 
-            recursive: args.is_present("recursive"),
+            recursive: args.is_present("recursive") || args.is_present("pick"),
             dir: args.is_present("dir"),
             verbose: args.is_present("verbose"),
             file: args
                 .get_many::<OsString>("FILE")
                 .map(|t| t.map(ToOwned::to_owned).collect())
                 .unwrap_or_default(),
-            follow_symlinks: args.is_present("follow_links"),
+            follow_symlinks: match args.value_of("follow_links") {
+                Some("inside") => FollowLinks::Inside,
+                Some(_) => FollowLinks::Any,
+                None => FollowLinks::Never,
+            },
             rip: args.is_present("rip"),
+            threads: args.get_one::<u16>("threads").map(|&n| usize::from(n)),
             trash: args.is_present("trash"),
             shred: args.is_present("shred"),
+            wipe_free_space: args.value_of_os("wipe_free_space").map(ToOwned::to_owned),
+            dry_run: args.is_present("dry_run"),
+            pick: args.is_present("pick"),
+            progress: args.is_present("progress"),
+            summary: args.is_present("summary"),
         }
     }
 }
 
-#[must_use]
-#[inline]
-pub fn interact_level(opt: &RmOptions, args: &ArgMatches) -> InteractiveMode {
-    match args {
-        x if x.is_present("interactive_always") => InteractiveMode::Always,
-        x if x.is_present("interactive_once") => InteractiveMode::Once,
-        _ => opt.interactive,
+/// `--answers`, or `RMX_ANSWERS` unless told what to assume
+fn answers(args: &ArgMatches) -> Option<Vec<String>> {
+    let list = match args.value_of("answers") {
+        Some(list) => list.to_owned(),
+        None if args.is_present("assume_yes") || args.is_present("assume_no") => return None,
+        None => std::env::var("RMX_ANSWERS").ok()?,
+    };
+
+    Some(
+        list.split(',')
+            .map(|answer| answer.trim().to_owned())
+            .collect(),
+    )
+}
+
+/// Like GNU `rm`, whichever of `-f`, `-i`, `-I` and `--interactive` comes last decides how to
+/// prompt and whether nonexistent files are ignored, which `--interactive=never` leaves as is
+fn interactivity(args: &ArgMatches) -> (InteractiveMode, bool) {
+    let mut flags = Vec::new();
+
+    for (id, mode, force) in [
+        ("force", InteractiveMode::Never, Some(true)),
+        ("interactive_always", InteractiveMode::Always, Some(false)),
+        ("interactive_once", InteractiveMode::Once, Some(false)),
+    ] {
+        if let Some(index) = args.indices_of(id).and_then(Iterator::max) {
+            flags.push((index, mode, force));
+        }
     }
+
+    if let (Some(indices), Some(values)) = (args.indices_of("WHEN"), args.values_of("WHEN")) {
+        for (index, when) in indices.zip(values) {
+            let (mode, force) = match when {
+                "never" => (InteractiveMode::Never, None),
+                "once" => (InteractiveMode::Once, Some(false)),
+                _ => (InteractiveMode::Always, Some(false)),
+            };
+            flags.push((index, mode, force));
+        }
+    }
+
+    flags.sort_unstable_by_key(|&(index, ..)| index);
+    flags.into_iter().fold(
+        (InteractiveMode::default(), false),
+        |(_, ignore), (_, mode, force)| (mode, force.unwrap_or(ignore)),
+    )
 }
@@ -1,11 +1,14 @@
-use std::ffi::{OsStr, OsString};
-use std::{fs, io, path};
-
-#[cfg(unix)]
-use std::os::unix::fs::MetadataExt;
+use std::cell::Cell;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::{fs, io};
 
 use crate::arg::RmOptions;
 use crate::error::Error;
+use crate::fd::{Dir, Kind, Stat};
+use crate::interact::Prompts;
+use crate::policy::Protected;
+use crate::progress::Progress;
 use crate::shred;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -15,48 +18,351 @@ pub const BIN_NAME: &str = env!("CARGO_BIN_NAME");
 pub enum RmStatus {
     Accept,
     Declined,
+    /// Declined, leaving the rest of a directory alone: the one asked about, or else the one
+    /// holding the entry
+    Skipped,
     Failed(Error),
 }
 
+/// Where an entry lives: the directory holding it and its name there, which is what removal goes
+/// through. The full path is only handed to path based APIs like the trash bin or `shred`
+pub struct Location<'a> {
+    pub parent: &'a Dir,
+    pub name: &'a OsStr,
+    pub path: &'a Path,
+    /// What became of the attributes of `parent`, for entries right in it
+    pub holder: Option<&'a Cell<Holder>>,
+}
+
+/// What `--clear-immutable` did to a directory whose entries are being removed, so it's asked
+/// about and cleared once for all of them, and restored once done with them
+#[derive(Debug, Default, Clone, Copy)]
+pub enum Holder {
+    #[default]
+    Untouched,
+    /// Its flags before they were cleared
+    Cleared(u32),
+    /// Clearing was declined or failed
+    Kept,
+}
+
 #[derive(Debug)]
 pub enum FsEntity {
     Symlink {
-        metadata: fs::Metadata,
+        stat: Stat,
         name: String,
-        inode_id: u64,
     },
     Dir {
-        metadata: fs::Metadata,
+        stat: Stat,
         name: String,
-        inode_id: u64,
     },
     File {
-        metadata: fs::Metadata,
+        stat: Stat,
+        name: String,
+    },
+    #[cfg(unix)]
+    Fifo {
+        stat: Stat,
+        name: String,
+    },
+    #[cfg(unix)]
+    Socket {
+        stat: Stat,
+        name: String,
+    },
+    #[cfg(unix)]
+    CharDevice {
+        stat: Stat,
+        name: String,
+    },
+    #[cfg(unix)]
+    BlockDevice {
+        stat: Stat,
         name: String,
-        inode_id: u64,
     },
 }
 
-#[cfg(unix)]
-#[must_use]
-pub fn is_write_protected(metadata: &fs::Metadata) -> bool {
-    let file_uid = metadata.uid();
-    let proc_uid = unsafe { libc::getuid() };
+impl FsEntity {
+    pub const fn stat(&self) -> &Stat {
+        match self {
+            Self::Symlink { stat, .. } | Self::Dir { stat, .. } | Self::File { stat, .. } => stat,
+            #[cfg(unix)]
+            Self::Fifo { stat, .. }
+            | Self::Socket { stat, .. }
+            | Self::CharDevice { stat, .. }
+            | Self::BlockDevice { stat, .. } => stat,
+        }
+    }
 
-    metadata.permissions().readonly() || file_uid != proc_uid
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Symlink { name, .. } | Self::Dir { name, .. } | Self::File { name, .. } => name,
+            #[cfg(unix)]
+            Self::Fifo { name, .. }
+            | Self::Socket { name, .. }
+            | Self::CharDevice { name, .. }
+            | Self::BlockDevice { name, .. } => name,
+        }
+    }
+
+    /// The type of the entry as GNU `rm` words it in prompts
+    pub const fn file_type(&self) -> &'static str {
+        match self {
+            Self::Symlink { .. } => "symbolic link",
+            Self::Dir { .. } => "directory",
+            Self::File { .. } => "regular file",
+            #[cfg(unix)]
+            Self::Fifo { .. } => "fifo",
+            #[cfg(unix)]
+            Self::Socket { .. } => "socket",
+            #[cfg(unix)]
+            Self::CharDevice { .. } => "character special file",
+            #[cfg(unix)]
+            Self::BlockDevice { .. } => "block special file",
+        }
+    }
 }
 
-#[cfg(windows)]
+/// Whether GNU `rm` deems `at` write-protected and prompts for it: `access(2)` with the effective
+/// ids decides, so root gets to write anything. Symlinks can't be written to and never count
 #[must_use]
-pub fn is_write_protected(metadata: &fs::Metadata) -> bool {
-    metadata.permissions().readonly()
+pub fn is_write_protected(at: &Location, stat: &Stat) -> bool {
+    stat.kind != Kind::Symlink && !at.parent.writable_at(at.name)
 }
 
-pub fn is_empty_dir(path: &OsStr) -> bool {
-    fs::read_dir(path)
-        .expect("path to be a directory")
-        .next()
-        .is_none()
+/// The directory holding `at`, relative to `at.parent`
+#[cfg(unix)]
+fn holder<'a>(at: &Location<'a>) -> &'a OsStr {
+    Path::new(at.name)
+        .parent()
+        .filter(|holder| !holder.as_os_str().is_empty())
+        .map_or_else(|| OsStr::new("."), Path::as_os_str)
+}
+
+/// Why the directory holding `at` won't let go of it: that directory has to be writable, and once
+/// it's sticky like `/tmp`, one of the two has to be owned unless running as root
+#[cfg(unix)]
+fn unlink_denied(at: &Location, stat: &Stat) -> Option<io::Error> {
+    let holder = holder(at);
+
+    if !at.parent.writable_at(holder) {
+        return Some(io::Error::from_raw_os_error(libc::EACCES));
+    }
+
+    is_sticky_for(at, stat, holder).then(|| io::Error::from_raw_os_error(libc::EPERM))
+}
+
+/// Whether `holder` is sticky and neither it nor the entry belong to the effective user
+#[cfg(unix)]
+fn is_sticky_for(at: &Location, stat: &Stat, holder: &OsStr) -> bool {
+    let euid = unsafe { libc::geteuid() };
+
+    at.parent
+        .stat_following_at(holder)
+        .is_ok_and(|dir| dir.mode & 0o1000 != 0 && euid != 0 && stat.uid != euid && dir.uid != euid)
+}
+
+/// Run `remove` on `at`, once more if `--clear-immutable` lifted what stood in its way. Whatever
+/// was lifted is put back on what's still there afterwards
+fn remove_at(
+    at: &Location,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+    prompts: &Prompts,
+    remove: fn(&Dir, &OsStr) -> io::Result<()>,
+) -> Result<()> {
+    remove(at.parent, at.name)
+        .or_else(|err| {
+            let cleared = clear_attributes(at, name, rel_root, opt, prompts, err)?;
+            let removed = remove(at.parent, at.name);
+            restore_attributes(at, &cleared, removed.is_ok());
+            removed
+        })
+        .map_err(|err| removal_error(at, concat_relative_root(rel_root, name), err))
+}
+
+/// With `--clear-immutable`, strip `chattr +i` and `+a` from `at` and the directory holding it
+/// after they made a removal fail with `err`. Anyone but root is asked first. Returns what is to be
+/// restored right after the removal along with its flags before, the directory is left to its
+/// `Holder` when it has one. `err` comes back when there was nothing to clear or it couldn't be
+#[cfg(target_os = "linux")]
+fn clear_attributes<'a>(
+    at: &Location<'a>,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+    prompts: &Prompts,
+    err: io::Error,
+) -> io::Result<Vec<(&'a OsStr, u32)>> {
+    use crate::fd::{FS_APPEND_FL, FS_IMMUTABLE_FL};
+
+    if !opt.clear_immutable || err.raw_os_error() != Some(libc::EPERM) {
+        return Err(err);
+    }
+
+    let Ok(stat) = at.parent.stat_at(at.name) else {
+        return Err(err);
+    };
+    let holder = holder(at);
+    // Opening anything else could block or have side effects
+    let targets = if matches!(stat.kind, Kind::File | Kind::Dir) {
+        vec![at.name, holder]
+    } else {
+        vec![holder]
+    };
+
+    // Only entries right in `at.parent` share what became of it
+    let shared = at.holder.filter(|_| holder == OsStr::new("."));
+
+    let mut cleared = Vec::new();
+    let mut lifted = false;
+    for target in targets {
+        let shared = shared.filter(|_| target == holder);
+        if shared.is_some_and(|shared| !matches!(shared.get(), Holder::Untouched)) {
+            continue;
+        }
+
+        let Ok(flags) = at.parent.inode_flags_at(target) else {
+            continue;
+        };
+        if flags & (FS_IMMUTABLE_FL | FS_APPEND_FL) == 0 {
+            continue;
+        }
+
+        // The directory shows up as the entries below it do
+        let relative_name = if target == at.name {
+            concat_relative_root(rel_root, name)
+        } else if rel_root.is_empty() {
+            holder.to_string_lossy().into_owned()
+        } else {
+            rel_root.to_owned()
+        };
+
+        let root = unsafe { libc::geteuid() } == 0;
+        if !root {
+            let message = format!(
+                "{BIN_NAME}: clear immutable and append-only attributes of '{relative_name}'?"
+            );
+            if !matches!(prompts.confirm(message), Ok(true)) {
+                shared.inspect(|shared| shared.set(Holder::Kept));
+                restore_attributes(at, &cleared, false);
+                return Err(err);
+            }
+        }
+
+        if at
+            .parent
+            .set_inode_flags_at(target, flags & !(FS_IMMUTABLE_FL | FS_APPEND_FL))
+            .is_err()
+        {
+            shared.inspect(|shared| shared.set(Holder::Kept));
+            restore_attributes(at, &cleared, false);
+            return Err(err);
+        }
+
+        if opt.verbose {
+            println!("cleared immutable and append-only attributes of '{relative_name}'");
+        }
+        match shared {
+            Some(shared) => shared.set(Holder::Cleared(flags)),
+            None => cleared.push((target, flags)),
+        }
+        lifted = true;
+    }
+
+    if lifted {
+        Ok(cleared)
+    } else {
+        Err(err)
+    }
+}
+
+/// Put back the flags `clear_attributes` lifted, on `at` itself only when it wasn't `removed`
+#[cfg(target_os = "linux")]
+fn restore_attributes(at: &Location, cleared: &[(&OsStr, u32)], removed: bool) {
+    for &(target, flags) in cleared {
+        if !(removed && target == at.name) {
+            let _ = at.parent.set_inode_flags_at(target, flags);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+#[allow(clippy::unnecessary_wraps)]
+const fn clear_attributes(
+    _at: &Location,
+    _name: &str,
+    _rel_root: &str,
+    _opt: &RmOptions,
+    _prompts: &Prompts,
+    err: io::Error,
+) -> io::Result<Vec<(&'static OsStr, u32)>> {
+    Err(err)
+}
+
+/// Put back the flags of a directory that's staying, once done with its entries
+#[cfg(target_os = "linux")]
+pub fn restore_holder(dir: &Dir, holder: Holder) {
+    if let Holder::Cleared(flags) = holder {
+        let _ = dir.set_inode_flags_at(OsStr::new("."), flags);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub const fn restore_holder(_dir: &Dir, _holder: Holder) {}
+
+#[cfg(not(target_os = "linux"))]
+const fn restore_attributes(_at: &Location, _cleared: &[(&OsStr, u32)], _removed: bool) {}
+
+/// Map a failed removal of `at` to an error, telling apart the causes behind a bare `EPERM`: the
+/// immutable and append-only attributes of the entry or the directory holding it, or that
+/// directory being sticky
+#[cfg_attr(windows, allow(unused_variables))]
+pub fn removal_error(at: &Location, relative_name: String, err: io::Error) -> Error {
+    #[cfg(unix)]
+    if err.raw_os_error() == Some(libc::EPERM) {
+        if let Some(cause) = explain_not_permitted(at) {
+            return cause(relative_name);
+        }
+    }
+
+    Error::from_io(relative_name, err)
+}
+
+#[cfg(unix)]
+fn explain_not_permitted(at: &Location) -> Option<fn(String) -> Error> {
+    let stat = at.parent.stat_at(at.name).ok()?;
+    let holder = holder(at);
+
+    #[cfg(target_os = "linux")]
+    {
+        use crate::fd::{FS_APPEND_FL, FS_IMMUTABLE_FL};
+
+        // Opening anything else could block or have side effects
+        if matches!(stat.kind, Kind::File | Kind::Dir) {
+            let flags = at.parent.inode_flags_at(at.name).unwrap_or(0);
+            if flags & FS_IMMUTABLE_FL != 0 {
+                return Some(Error::Immutable);
+            }
+            if flags & FS_APPEND_FL != 0 {
+                return Some(Error::AppendOnly);
+            }
+        }
+
+        let flags = at.parent.inode_flags_at(holder).unwrap_or(0);
+        if flags & (FS_IMMUTABLE_FL | FS_APPEND_FL) != 0 {
+            return Some(Error::DirectoryAttribute);
+        }
+    }
+
+    is_sticky_for(at, &stat, holder).then_some(Error::StickyDirectory as fn(String) -> Error)
+}
+
+/// Windows refuses to remove read-only entries, whatever the directory holding them allows
+#[cfg(windows)]
+fn unlink_denied(at: &Location, _stat: &Stat) -> Option<io::Error> {
+    (!at.parent.writable_at(at.name)).then(|| io::Error::from(io::ErrorKind::PermissionDenied))
 }
 
 pub fn concat_relative_root(rel_root: &str, name: &str) -> String {
@@ -69,108 +375,211 @@ pub fn concat_relative_root(rel_root: &str, name: &str) -> String {
 }
 
 pub fn unlink_dir(
-    path: &OsStr,
+    at: &Location,
+    stat: &Stat,
     name: &str,
     rel_root: &str,
-    visited: bool,
     opt: &RmOptions,
-) -> Result<bool> {
-    if !is_empty_dir(path) && !visited {
-        return Ok(false);
-    }
+    prompts: &Prompts,
+    progress: &Progress,
+) -> Result<()> {
+    if opt.dry_run {
+        // Nothing is attempted, so what the directory holding it allows is checked up front
+        if let Some(err) = unlink_denied(at, stat) {
+            return Err(removal_error(at, concat_relative_root(rel_root, name), err));
+        }
 
-    if is_write_protected(&fs::metadata(path)?) {
         let relative_name = concat_relative_root(rel_root, name);
-        return Err(Error::OperationNotPermitted(relative_name));
+        println!("would remove directory '{relative_name}'");
+        progress.removed(Kind::Dir, 0);
+        return Ok(());
     }
 
     if opt.trash {
-        trash::delete(path)?;
+        trash::delete(at.path)?;
     } else {
-        fs::remove_dir(path).map_err(|err| match err.kind() {
-            io::ErrorKind::PermissionDenied => {
-                let relative_name = concat_relative_root(rel_root, name);
-                Error::OperationNotPermitted(relative_name)
-            }
-            _ => Error::Io(err),
-        })?;
+        remove_at(at, name, rel_root, opt, prompts, Dir::remove_dir_at)?;
     }
 
     if opt.verbose {
         let relative_name = concat_relative_root(rel_root, name);
-        println!("directory '{}' was removed", relative_name);
+        println!("directory '{relative_name}' was removed");
     }
+    progress.removed(Kind::Dir, 0);
 
-    Ok(true)
+    Ok(())
 }
 
-pub fn unlink_file(path: &OsStr, name: &str, rel_root: &str, opt: &RmOptions) -> Result<()> {
+pub fn unlink_file(
+    at: &Location,
+    stat: &Stat,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+    prompts: &Prompts,
+    progress: &Progress,
+) -> Result<()> {
+    if opt.dry_run {
+        if let Some(err) = unlink_denied(at, stat) {
+            return Err(removal_error(at, concat_relative_root(rel_root, name), err));
+        }
+
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("would remove '{relative_name}'");
+        progress.removed(Kind::File, stat.size);
+        return Ok(());
+    }
+
     if opt.trash {
-        trash::delete(path)?;
+        trash::delete(at.path)?;
     } else if opt.shred {
-        shred::shred(path)?;
+        shred::shred(at.path.as_os_str())?;
     } else {
-        fs::remove_file(path).map_err(|err| match err.kind() {
-            io::ErrorKind::PermissionDenied => {
-                let relative_name = concat_relative_root(rel_root, name);
-                Error::PermissionDenied(relative_name)
-            }
-            _ => Error::Io(err),
-        })?;
+        remove_at(at, name, rel_root, opt, prompts, Dir::unlink_at)?;
     }
 
     if opt.verbose {
         let relative_name = concat_relative_root(rel_root, name);
-        println!("removed '{}'", relative_name);
+        println!("removed '{relative_name}'");
     }
+    progress.removed(Kind::File, stat.size);
 
     Ok(())
 }
 
-pub fn unlink_symlink(path: &OsStr, name: &str, rel_root: &str, opt: &RmOptions) -> Result<()> {
+pub fn unlink_symlink(
+    at: &Location,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+    prompts: &Prompts,
+    progress: &Progress,
+) -> Result<()> {
+    if opt.dry_run {
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("would remove '{relative_name}'");
+        progress.removed(Kind::Symlink, 0);
+        return Ok(());
+    }
+
     if opt.trash {
-        trash::delete(path)?;
+        trash::delete(at.path)?;
     } else {
-        fs::remove_file(path)?;
+        remove_at(at, name, rel_root, opt, prompts, Dir::unlink_at)?;
     }
 
     if opt.verbose {
         let relative_name = concat_relative_root(rel_root, name);
-        println!("removed '{}'", relative_name);
+        println!("removed '{relative_name}'");
     }
+    progress.removed(Kind::Symlink, 0);
 
     Ok(())
 }
 
-pub fn fs_entity(path: &OsStr) -> Result<FsEntity> {
-    let name = path::Path::new(path)
-        .file_name()
-        .map(|t| t.to_string_lossy().into_owned())
-        .unwrap_or_default();
-    let metadata = fs::symlink_metadata(path).map_err(|_| Error::NoSuchFile(name.clone()))?;
+/// FIFOs, sockets and device nodes only lose their name, there's no data to shred
+#[cfg(unix)]
+pub fn unlink_special(
+    at: &Location,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+    prompts: &Prompts,
+    progress: &Progress,
+) -> Result<()> {
+    if opt.dry_run {
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("would remove '{relative_name}'");
+        progress.removed(Kind::Other, 0);
+        return Ok(());
+    }
 
-    #[cfg(unix)]
-    let inode_id = metadata.dev();
-
-    #[cfg(windows)]
-    let inode_id = 0_u64;
-
-    let entity = match metadata {
-        m if m.is_dir() => FsEntity::Dir {
-            metadata: m,
-            name,
-            inode_id,
-        },
-        m if m.is_symlink() => FsEntity::Symlink {
-            metadata: m,
-            name,
-            inode_id,
-        },
-        m if m.is_file() => FsEntity::File {
-            metadata: m,
-            name,
-            inode_id,
-        },
+    if opt.trash {
+        trash::delete(at.path)?;
+    } else {
+        remove_at(at, name, rel_root, opt, prompts, Dir::unlink_at)?;
+    }
+
+    if opt.verbose {
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("removed '{relative_name}'");
+    }
+    progress.removed(Kind::Other, 0);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn shred_block_device(
+    at: &Location,
+    name: &str,
+    rel_root: &str,
+    opt: &RmOptions,
+) -> Result<()> {
+    if opt.dry_run {
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("would shred '{relative_name}'");
+        return Ok(());
+    }
+
+    shred::shred_device(at.path.as_os_str())?;
+
+    if opt.verbose {
+        let relative_name = concat_relative_root(rel_root, name);
+        println!("shredded '{relative_name}'");
+    }
+
+    Ok(())
+}
+
+pub fn wipe_free_space(dir: &OsStr, opt: &RmOptions) -> Result<()> {
+    let name = dir.to_string_lossy().into_owned();
+
+    match fs::metadata(dir) {
+        Ok(metadata) if metadata.is_dir() => (),
+        Ok(_) => return Err(Error::WipeFreeSpace(name, "Not a directory".to_owned())),
+        Err(_) => {
+            return Err(Error::WipeFreeSpace(
+                name,
+                "No such file or directory".to_owned(),
+            ))
+        }
+    }
+
+    let wiped = shred::wipe_free_space(dir)
+        .map_err(|err| Error::WipeFreeSpace(name.clone(), err.to_string()))?;
+
+    if opt.verbose {
+        println!("wiped {wiped} bytes of free space in '{name}'");
+    }
+
+    Ok(())
+}
+
+pub fn fs_entity(at: &Location) -> Result<FsEntity> {
+    // Names like `..` or `/` have no file name, those are shown as is
+    let name = Path::new(at.name)
+        .file_name()
+        .unwrap_or(at.name)
+        .to_string_lossy()
+        .into_owned();
+    let stat = at
+        .parent
+        .stat_at(at.name)
+        .map_err(|_| Error::NoSuchFile(name.clone()))?;
+
+    let entity = match stat.kind {
+        Kind::Dir => FsEntity::Dir { stat, name },
+        Kind::Symlink => FsEntity::Symlink { stat, name },
+        Kind::File => FsEntity::File { stat, name },
+        #[cfg(unix)]
+        Kind::Fifo => FsEntity::Fifo { stat, name },
+        #[cfg(unix)]
+        Kind::Socket => FsEntity::Socket { stat, name },
+        #[cfg(unix)]
+        Kind::CharDevice => FsEntity::CharDevice { stat, name },
+        #[cfg(unix)]
+        Kind::BlockDevice => FsEntity::BlockDevice { stat, name },
         _ => {
             return Err(Error::UnknownEntity(name));
         }
@@ -179,71 +588,174 @@ pub fn fs_entity(path: &OsStr) -> Result<FsEntity> {
     Ok(entity)
 }
 
+/// With `--one-file-system`, report a directory that isn't on `root`, the device of the command
+/// line argument it was found under
 #[cfg(unix)]
-pub fn one_file_system(opt: &RmOptions, fullname: &str, parent: u64, child: u64) -> bool {
-    // This is either top path or we're not on unix
-    if parent == 0 {
+pub fn one_file_system(opt: &RmOptions, rel_root: &str, name: &str, root: u64, dev: u64) -> bool {
+    if !opt.one_file_system || root == dev {
         return false;
     }
 
-    if opt.one_file_system && parent != child {
-        println!(
-            "rm: skipping '{fullname}', since it's on a different device",
-            fullname = fullname
-        );
-        true
-    } else {
-        false
-    }
+    other_device(&concat_relative_root(rel_root, name), root, dev)
 }
 
-#[cfg(windows)]
-pub const fn one_file_system(_opt: &RmOptions, _fullname: &str, parent: u64, _child: u64) -> bool {
-    if parent == 0 {
+/// Report `fullname` as skipped if it doesn't live on the `root` device
+#[cfg(unix)]
+pub fn other_device(fullname: &str, root: u64, dev: u64) -> bool {
+    if root == dev {
         return false;
     }
 
+    println!("{BIN_NAME}: skipping '{fullname}', since it's on a different device");
+    true
+}
+
+#[cfg(windows)]
+pub const fn one_file_system(
+    _opt: &RmOptions,
+    _rel_root: &str,
+    _name: &str,
+    _root: u64,
+    _dev: u64,
+) -> bool {
     false
 }
 
-pub fn preserve_root(opt: &RmOptions, path: &OsStr) -> bool {
+#[cfg(unix)]
+const ROOT: &str = "/";
+#[cfg(windows)]
+const ROOT: &str = "C:\\";
+
+/// Refuse to remove `/`. Compares the inode of `stat`, the directory as it was inspected, so this
+/// stays cheap no matter how deep `path` is
+pub fn preserve_root(
+    opt: &RmOptions,
+    path: &Path,
+    rel_root: &str,
+    name: &str,
+    stat: &Stat,
+) -> bool {
     #[cfg(not(any(windows, unix)))]
     {
         println!("rm: unsupported");
         return true;
     }
 
-    if opt.no_preserve_root {
+    if opt.no_preserve_root || !is_root(path, stat) {
         return false;
     }
 
-    #[cfg(unix)]
-    let pred = if opt.preserve_root == "all" {
-        OsString::from("/")
+    let fullname = concat_relative_root(rel_root, name);
+    if fullname == ROOT {
+        println!("{BIN_NAME}: it is dangerous to operate recursively on '{ROOT}'");
     } else {
-        opt.preserve_root.clone()
+        println!(
+            "{BIN_NAME}: it is dangerous to operate recursively on '{fullname}' (same as '{ROOT}')"
+        );
+    }
+    println!("{BIN_NAME}: use --no-preserve-root to override this failsafe");
+
+    true
+}
+
+/// Refuse to remove or descend into anything passed to `--protect` or matching a policy file
+pub fn protected(
+    protected: &Protected,
+    path: &Path,
+    rel_root: &str,
+    name: &str,
+    stat: &Stat,
+) -> bool {
+    let Some(label) = protected.find(path, stat) else {
+        return false;
     };
 
-    #[cfg(windows)]
-    let pred = if opt.preserve_root == "all" {
-        OsString::from("C:\\")
-    } else {
-        opt.preserve_root.clone()
+    let fullname = concat_relative_root(rel_root, name);
+    println!("{BIN_NAME}: refusing to remove '{fullname}': skipping (protected '{label}')");
+
+    true
+}
+
+/// Refuse a command line argument or a followed link target that's below something protected,
+/// naming it directly doesn't get around the protection
+pub fn protected_above(protected: &Protected, path: &Path, rel_root: &str, name: &str) -> bool {
+    let Some(label) = protected.find_above(path) else {
+        return false;
     };
 
-    let maybe_path = path::Path::new(path).canonicalize().ok();
-    let maybe_pred = path::Path::new(&pred).canonicalize().ok();
+    let fullname = concat_relative_root(rel_root, name);
+    println!("{BIN_NAME}: refusing to remove '{fullname}': skipping (protected '{label}')");
+
+    true
+}
+
+/// Refuse a command line argument whose last component is `.` or `..`, like `dir/.` or `../`
+pub fn dot_or_dotdot(path: &OsStr) -> bool {
+    let name = path.to_string_lossy();
+    let trimmed = name.trim_end_matches(std::path::is_separator);
+    let last = trimmed
+        .rsplit(std::path::is_separator)
+        .next()
+        .unwrap_or(trimmed);
+
+    if last != "." && last != ".." {
+        return false;
+    }
+
+    println!("{BIN_NAME}: refusing to remove '.' or '..' directory: skipping '{name}'");
+    true
+}
+
+/// With `--preserve-root=all`, reject a command line argument that's on a different device than
+/// its parent, like a mount point
+#[cfg(unix)]
+pub fn preserve_all_root(opt: &RmOptions, path: &OsStr, stat: &Stat) -> bool {
+    if !opt.preserve_all_root {
+        return false;
+    }
+
+    let name = path.to_string_lossy();
+    let parent = Path::new(path).join("..");
 
-    if let (Some(fullpath), Some(fullpred)) = (maybe_path, maybe_pred) {
-        if fullpath == fullpred {
+    match Dir::cwd().stat_at(parent.as_os_str()) {
+        Ok(parent) if parent.dev == stat.dev => false,
+        Ok(_) => {
+            println!("{BIN_NAME}: skipping '{name}', since it's on a different device");
+            println!("{BIN_NAME}: and --preserve-root=all is in effect");
+            true
+        }
+        Err(err) => {
             println!(
-                "rm: refusing to remove '{path}': skipping (preserve-root='{pred}')",
-                path = path.to_string_lossy(),
-                pred = pred.to_string_lossy()
+                "{BIN_NAME}: failed to stat '{}': skipping: {err}",
+                parent.display()
             );
-            return true;
+            true
         }
     }
+}
 
+#[cfg(windows)]
+pub const fn preserve_all_root(_opt: &RmOptions, _path: &OsStr, _stat: &Stat) -> bool {
     false
 }
+
+/// Whether `stat` is `/`, which is only looked up once for the whole run
+#[cfg(unix)]
+fn is_root(_path: &Path, stat: &Stat) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::OnceLock;
+
+    static ROOT_ID: OnceLock<Option<(u64, u64)>> = OnceLock::new();
+
+    let root = ROOT_ID.get_or_init(|| fs::metadata(ROOT).ok().map(|m| (m.dev(), m.ino())));
+    *root == Some((stat.dev, stat.ino))
+}
+
+/// No inode numbers to go by here
+#[cfg(windows)]
+fn is_root(path: &Path, _stat: &Stat) -> bool {
+    match (path.canonicalize(), Path::new(ROOT).canonicalize()) {
+        (Ok(path), Ok(other)) => path == other,
+        _ => false,
+    }
+}
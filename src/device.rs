@@ -0,0 +1,18 @@
+use crate::core::{concat_relative_root, RmStatus, BIN_NAME};
+use crate::interact::Prompts;
+
+/// Overwriting a whole device is never implied by `-f`, the interactive mode, `--assume-yes`,
+/// `--answers` or an earlier all, its name has to be typed out on the terminal
+#[must_use]
+pub fn prompt(name: &str, rel_root: &str, prompts: &Prompts) -> RmStatus {
+    let relative_name = concat_relative_root(rel_root, name);
+    let message = format!(
+        "{BIN_NAME}: shred block special file '{relative_name}'? all data on the device will be lost"
+    );
+
+    match prompts.confirm_typed(message, &relative_name) {
+        Ok(true) => RmStatus::Accept,
+        Ok(false) => RmStatus::Declined,
+        Err(err) => RmStatus::Failed(err),
+    }
+}
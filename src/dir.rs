@@ -1,23 +1,22 @@
-use std::ffi::OsStr;
-use std::fs;
-
 use crate::arg::{InteractiveMode, RmOptions};
-use crate::core::{concat_relative_root, is_empty_dir, is_write_protected, RmStatus, BIN_NAME};
+use crate::core::{concat_relative_root, RmStatus, BIN_NAME};
 use crate::error::Error;
-use crate::interact;
+use crate::interact::Prompts;
+use crate::scan::Usage;
 
 #[must_use]
+#[allow(clippy::too_many_arguments)]
 pub fn prompt(
     opt: &RmOptions,
-    path: &OsStr,
+    is_empty_dir: bool,
     rel_root: &str,
-    metadata: &fs::Metadata,
+    write_protected: bool,
     name: &str,
     mode: InteractiveMode,
     visited: bool,
+    usage: impl FnOnce() -> Usage,
+    prompts: &Prompts,
 ) -> RmStatus {
-    let is_empty_dir = is_empty_dir(path);
-
     if !opt.recursive {
         if !opt.dir {
             return RmStatus::Failed(Error::IsDirectory(name.to_owned()));
@@ -28,44 +27,36 @@ pub fn prompt(
         }
     }
 
-    let write_protected = is_write_protected(metadata);
     let descend = opt.recursive && !is_empty_dir && !visited;
-    let message = format!(
-        "{bin}: {descend_remove}{write_protected}directory '{relative_name}'?",
-        bin = BIN_NAME,
-        descend_remove = if descend { "descend into" } else { "remove" },
-        write_protected = if write_protected {
-            " write-protected "
-        } else {
-            " "
-        },
-        relative_name = concat_relative_root(rel_root, name)
-    );
-
-    let maybe_interact = match mode {
-        InteractiveMode::Always => {
-            if (is_empty_dir && opt.dir) || opt.recursive {
-                interact::with_message(message)
-            } else {
-                Ok(true)
-            }
-        }
-        InteractiveMode::Once => Ok(true),
-        InteractiveMode::Never => {
-            if (opt.dir || opt.recursive) && write_protected && !opt.force {
-                interact::with_message(message)
-            } else {
-                Ok(true)
-            }
+    let ask = match mode {
+        InteractiveMode::Always => (is_empty_dir && opt.dir) || opt.recursive,
+        InteractiveMode::Once | InteractiveMode::WriteProtected => {
+            (opt.dir || opt.recursive) && write_protected && prompts.asks_write_protected()
         }
+        InteractiveMode::Never => false,
     };
 
-    if let Ok(yes) = maybe_interact {
-        if yes {
-            return RmStatus::Accept;
-        }
-        return RmStatus::Declined;
+    if !ask {
+        return RmStatus::Accept;
     }
 
-    RmStatus::Failed(maybe_interact.unwrap_err())
+    prompts.ask(|| {
+        format!(
+            "{bin}: {descend_remove}{write_protected}directory '{relative_name}'{usage}?",
+            bin = BIN_NAME,
+            descend_remove = if descend { "descend into" } else { "remove" },
+            write_protected = if write_protected {
+                " write-protected "
+            } else {
+                " "
+            },
+            relative_name = concat_relative_root(rel_root, name),
+            // Scanned only now, what's below is only worth telling when about to go into it
+            usage = if descend {
+                format!(" ({})", usage())
+            } else {
+                String::new()
+            }
+        )
+    })
 }
@@ -1,3 +1,5 @@
+use std::io;
+
 use thiserror::Error;
 
 use crate::core::BIN_NAME;
@@ -22,6 +24,71 @@ pub enum Error {
     #[error("{}", fmt_error("Unknown file system entity", Some(.0)))]
     UnknownEntity(String),
 
+    #[error(
+        "{}",
+        fmt_error(
+            "Operation not permitted: it is immutable, 'chattr -i' as root lifts that",
+            Some(.0)
+        )
+    )]
+    Immutable(String),
+
+    #[error(
+        "{}",
+        fmt_error(
+            "Operation not permitted: it is append-only, 'chattr -a' as root lifts that",
+            Some(.0)
+        )
+    )]
+    AppendOnly(String),
+
+    #[error(
+        "{}",
+        fmt_error(
+            "Operation not permitted: the directory holding it is immutable or append-only, \
+            'chattr -i -a' on it as root lifts that",
+            Some(.0)
+        )
+    )]
+    DirectoryAttribute(String),
+
+    #[error(
+        "{}",
+        fmt_error(
+            "Operation not permitted: the directory holding it is sticky, only the owner of \
+            either or root may remove it",
+            Some(.0)
+        )
+    )]
+    StickyDirectory(String),
+
+    #[error("{}: not following symbolic link loop '{}'", BIN_NAME, .0)]
+    SymlinkLoop(String),
+
+    #[error("{}: skipping '{}': circular directory structure", BIN_NAME, .0)]
+    DirectoryCycle(String),
+
+    #[error("{}: not following '{}': target is outside the directory being removed", BIN_NAME, .0)]
+    OutsideTree(String),
+
+    #[error("{}", fmt_error(&describe(.1), Some(.0)))]
+    Os(String, io::Error),
+
+    #[error(
+        "{}: failed to remove {} {}",
+        BIN_NAME,
+        .0,
+        if *.0 == 1 { "entry" } else { "entries" }
+    )]
+    Incomplete(usize),
+
+    /// Whatever was left behind was reported along the way, like GNU `rm` nothing more is said
+    #[error("")]
+    Failed,
+
+    #[error("{}: cannot wipe free space in '{}': {}", BIN_NAME, .0, .1)]
+    WipeFreeSpace(String, String),
+
     #[error("{}: failed to access system trash bin", BIN_NAME)]
     TrashBin(#[from] trash::Error),
 
@@ -32,11 +99,50 @@ pub enum Error {
     )]
     Usage,
 
+    #[error(
+        "{}: cannot prompt without a terminal, answer with --assume-yes or --assume-no",
+        BIN_NAME
+    )]
+    NoTerminal,
+
+    #[error("{}: ran out of answers given with --answers or RMX_ANSWERS", BIN_NAME)]
+    AnswersExhausted,
+
+    #[error("{}: invalid answer '{}' given with --answers or RMX_ANSWERS", BIN_NAME, .0)]
+    InvalidAnswer(String),
+
     #[error("{}: cannot remove: {}", BIN_NAME, .0)]
-    Io(#[from] std::io::Error),
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Map a failed removal of `name` to what GNU `rm` would report
+    pub fn from_io(name: String, err: io::Error) -> Self {
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(libc::EPERM) {
+            return Self::OperationNotPermitted(name);
+        }
+
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(name),
+            io::ErrorKind::NotFound => Self::NoSuchFile(name),
+            io::ErrorKind::DirectoryNotEmpty => Self::DirectoryNotEmpty(name),
+            io::ErrorKind::IsADirectory => Self::IsDirectory(name),
+            _ => Self::Os(name, err),
+        }
+    }
+}
+
+/// `strerror` without the "(os error N)" suffix std appends
+fn describe(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.split_once(" (os error ") {
+        Some((cause, _)) => cause.to_owned(),
+        None => message,
+    }
 }
 
 fn fmt_error(cause: &str, maybe_name: Option<&str>) -> String {
-    let name = maybe_name.map_or_else(|| String::from(""), |name| format!(" '{}'", name));
-    format!(r"rmx: cannot remove{}: {}", name, cause)
+    let name = maybe_name.map_or_else(String::new, |name| format!(" '{name}'"));
+    format!(r"rmx: cannot remove{name}: {cause}")
 }
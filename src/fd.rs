@@ -0,0 +1,554 @@
+//! Directory handles for fd relative traversal. Entries are always looked up relative to an open
+//! directory without following symlinks, so swapping a directory for a symlink mid-run can't
+//! redirect a removal somewhere else, the same guarantee GNU `rm` gets from fts.
+
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+
+#[cfg(windows)]
+use std::fs;
+#[cfg(windows)]
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Kind {
+    Dir,
+    Symlink,
+    File,
+    Fifo,
+    Socket,
+    CharDevice,
+    BlockDevice,
+    Other,
+}
+
+/// `_IOR('f', 1, long)`, libc doesn't have it
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_possible_truncation)]
+const FS_IOC_GETFLAGS: libc::c_ulong = (2 << 30)
+    | ((std::mem::size_of::<libc::c_long>() as libc::c_ulong) << 16)
+    | ((b'f' as libc::c_ulong) << 8)
+    | 1;
+
+/// `_IOW('f', 2, long)`
+#[cfg(target_os = "linux")]
+#[allow(clippy::cast_possible_truncation)]
+const FS_IOC_SETFLAGS: libc::c_ulong = (1 << 30)
+    | ((std::mem::size_of::<libc::c_long>() as libc::c_ulong) << 16)
+    | ((b'f' as libc::c_ulong) << 8)
+    | 2;
+
+/// `chattr +i`, neither the inode nor its name can change
+#[cfg(target_os = "linux")]
+pub const FS_IMMUTABLE_FL: u32 = 0x10;
+
+/// `chattr +a`, only appending is allowed
+#[cfg(target_os = "linux")]
+pub const FS_APPEND_FL: u32 = 0x20;
+
+/// The parts of `stat(2)` traversal cares about
+#[derive(Debug, Copy, Clone)]
+pub struct Stat {
+    pub kind: Kind,
+    /// Permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub size: u64,
+    pub dev: u64,
+    pub ino: u64,
+}
+
+impl Stat {
+    #[must_use]
+    pub const fn same_inode(&self, other: &Self) -> bool {
+        self.dev == other.dev && self.ino == other.ino
+    }
+}
+
+/// An open directory, or the current working directory
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct Dir {
+    fd: RawFd,
+}
+
+#[cfg(unix)]
+impl Dir {
+    /// Resolves names like `open(2)` does, used for command line operands
+    #[must_use]
+    pub const fn cwd() -> Self {
+        Self { fd: libc::AT_FDCWD }
+    }
+
+    /// Open the directory `name`, failing if it's anything else, symlinks included
+    pub fn open_at(&self, name: &OsStr) -> io::Result<Self> {
+        self.open(name, libc::O_NOFOLLOW)
+    }
+
+    /// Open the directory `name` resolves to, through symlinks if need be
+    pub fn open_following(&self, name: &OsStr) -> io::Result<Self> {
+        self.open(name, 0)
+    }
+
+    fn open(&self, name: &OsStr, flags: libc::c_int) -> io::Result<Self> {
+        let name = c_name(name)?;
+        let flags = flags | libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(self.fd, name.as_ptr(), flags) };
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd })
+    }
+
+    pub fn stat(&self) -> io::Result<Stat> {
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+
+        if unsafe { libc::fstat(self.fd, stat.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Stat::from(unsafe { stat.assume_init() }))
+    }
+
+    /// `lstat(2)` relative to this directory
+    pub fn stat_at(&self, name: &OsStr) -> io::Result<Stat> {
+        self.fstatat(name, libc::AT_SYMLINK_NOFOLLOW)
+    }
+
+    /// `stat(2)` relative to this directory, through symlinks
+    pub fn stat_following_at(&self, name: &OsStr) -> io::Result<Stat> {
+        self.fstatat(name, 0)
+    }
+
+    fn fstatat(&self, name: &OsStr, flags: libc::c_int) -> io::Result<Stat> {
+        let name = c_name(name)?;
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+
+        if unsafe { libc::fstatat(self.fd, name.as_ptr(), stat.as_mut_ptr(), flags) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Stat::from(unsafe { stat.assume_init() }))
+    }
+
+    /// Whether `faccessat(2)` lets the effective ids write to `name`, which accounts for group
+    /// and other bits, ACLs, read-only mounts and root alike
+    #[must_use]
+    pub fn writable_at(&self, name: &OsStr) -> bool {
+        let Ok(name) = c_name(name) else {
+            return false;
+        };
+
+        unsafe { libc::faccessat(self.fd, name.as_ptr(), libc::W_OK, libc::AT_EACCESS) == 0 }
+    }
+
+    /// Inode flags of what `name` resolves to, as `lsattr` shows them. Only safe on regular files
+    /// and directories, opening anything else may have side effects
+    #[cfg(target_os = "linux")]
+    pub fn inode_flags_at(&self, name: &OsStr) -> io::Result<u32> {
+        let mut attributes = 0;
+        self.inode_flags_ioctl(name, FS_IOC_GETFLAGS, &mut attributes)?;
+
+        #[allow(clippy::cast_sign_loss)]
+        Ok(attributes as u32)
+    }
+
+    /// Replace the inode flags of what `name` resolves to, like `chattr` does
+    #[cfg(target_os = "linux")]
+    pub fn set_inode_flags_at(&self, name: &OsStr, flags: u32) -> io::Result<()> {
+        #[allow(clippy::cast_possible_wrap)]
+        let mut attributes = flags as libc::c_int;
+        self.inode_flags_ioctl(name, FS_IOC_SETFLAGS, &mut attributes)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn inode_flags_ioctl(
+        &self,
+        name: &OsStr,
+        request: libc::c_ulong,
+        attributes: &mut libc::c_int,
+    ) -> io::Result<()> {
+        let name = c_name(name)?;
+        let flags = libc::O_RDONLY | libc::O_NONBLOCK | libc::O_NOCTTY | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(self.fd, name.as_ptr(), flags) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // The kernel takes an int, whatever the size in the request says
+        let outcome = unsafe { libc::ioctl(fd, request as _, std::ptr::from_mut(attributes)) };
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+
+        if outcome == -1 {
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    pub fn unlink_at(&self, name: &OsStr) -> io::Result<()> {
+        self.unlinkat(name, 0)
+    }
+
+    pub fn remove_dir_at(&self, name: &OsStr) -> io::Result<()> {
+        self.unlinkat(name, libc::AT_REMOVEDIR)
+    }
+
+    fn unlinkat(&self, name: &OsStr, flags: libc::c_int) -> io::Result<()> {
+        let name = c_name(name)?;
+
+        if unsafe { libc::unlinkat(self.fd, name.as_ptr(), flags) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    pub fn read_link_at(&self, name: &OsStr) -> io::Result<PathBuf> {
+        let name = c_name(name)?;
+        let mut buf: Vec<u8> = Vec::with_capacity(256);
+
+        loop {
+            let len = unsafe {
+                libc::readlinkat(
+                    self.fd,
+                    name.as_ptr(),
+                    buf.as_mut_ptr().cast(),
+                    buf.capacity(),
+                )
+            };
+
+            if len == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Might have been truncated, try again with more room
+            if len as usize == buf.capacity() {
+                buf.reserve(buf.capacity());
+                continue;
+            }
+
+            unsafe { buf.set_len(len as usize) };
+            return Ok(PathBuf::from(OsString::from_vec(buf)));
+        }
+    }
+
+    /// Names in this directory, without `.` and `..`
+    pub fn entries(&self) -> io::Result<Vec<OsString>> {
+        // A fresh descriptor, so the stream owns its offset and closing it leaves `self` alone
+        let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        let fd = unsafe { libc::openat(self.fd, c".".as_ptr(), flags) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let stream = unsafe { libc::fdopendir(fd) };
+        if stream.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+
+        let mut entries = Vec::new();
+        let outcome = loop {
+            errno::clear();
+            let entry = unsafe { libc::readdir(stream) };
+
+            if entry.is_null() {
+                break match errno::get() {
+                    0 => Ok(()),
+                    errno => Err(io::Error::from_raw_os_error(errno)),
+                };
+            }
+
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+            if name != b"." && name != b".." {
+                entries.push(OsString::from_vec(name.to_vec()));
+            }
+        };
+
+        unsafe { libc::closedir(stream) };
+        outcome.map(|()| entries)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Dir {
+    fn drop(&mut self) {
+        if self.fd != libc::AT_FDCWD {
+            unsafe { libc::close(self.fd) };
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<libc::stat> for Stat {
+    #[allow(
+        clippy::useless_conversion,
+        clippy::unnecessary_cast,
+        clippy::cast_sign_loss
+    )]
+    fn from(stat: libc::stat) -> Self {
+        let kind = match stat.st_mode & libc::S_IFMT {
+            libc::S_IFDIR => Kind::Dir,
+            libc::S_IFLNK => Kind::Symlink,
+            libc::S_IFREG => Kind::File,
+            libc::S_IFIFO => Kind::Fifo,
+            libc::S_IFSOCK => Kind::Socket,
+            libc::S_IFCHR => Kind::CharDevice,
+            libc::S_IFBLK => Kind::BlockDevice,
+            _ => Kind::Other,
+        };
+
+        Self {
+            kind,
+            mode: u32::from(stat.st_mode) & 0o7777,
+            uid: stat.st_uid,
+            size: stat.st_size as u64,
+            dev: stat.st_dev as u64,
+            ino: stat.st_ino as u64,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn c_name(name: &OsStr) -> io::Result<CString> {
+    Ok(CString::new(name.as_bytes())?)
+}
+
+/// `readdir` only tells the end of a directory from an error through `errno`, which std doesn't
+/// let reset. Where it lives differs per libc, the same way std looks it up
+#[cfg(unix)]
+mod errno {
+    extern "C" {
+        #[cfg_attr(
+            any(
+                target_os = "linux",
+                target_os = "emscripten",
+                target_os = "fuchsia",
+                target_os = "l4re",
+                target_os = "hurd"
+            ),
+            link_name = "__errno_location"
+        )]
+        #[cfg_attr(
+            any(
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "android",
+                target_os = "redox",
+                target_env = "newlib"
+            ),
+            link_name = "__errno"
+        )]
+        #[cfg_attr(
+            any(target_os = "solaris", target_os = "illumos"),
+            link_name = "___errno"
+        )]
+        #[cfg_attr(target_os = "nto", link_name = "__get_errno_ptr")]
+        #[cfg_attr(
+            any(
+                target_os = "macos",
+                target_os = "ios",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "freebsd",
+                target_os = "dragonfly"
+            ),
+            link_name = "__error"
+        )]
+        #[cfg_attr(target_os = "haiku", link_name = "_errnop")]
+        #[cfg_attr(target_os = "aix", link_name = "_Errno")]
+        fn location() -> *mut libc::c_int;
+    }
+
+    pub fn clear() {
+        unsafe { *location() = 0 };
+    }
+
+    pub fn get() -> libc::c_int {
+        unsafe { *location() }
+    }
+}
+
+/// Windows has no `*at` family, so this falls back to paths
+#[cfg(windows)]
+#[derive(Debug)]
+pub struct Dir {
+    path: PathBuf,
+}
+
+#[cfg(windows)]
+impl Dir {
+    #[must_use]
+    pub const fn cwd() -> Self {
+        Self {
+            path: PathBuf::new(),
+        }
+    }
+
+    pub fn open_at(&self, name: &OsStr) -> io::Result<Self> {
+        let path = self.path.join(name);
+
+        if !fs::symlink_metadata(&path)?.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::Other, "Not a directory"));
+        }
+
+        Ok(Self { path })
+    }
+
+    pub fn open_following(&self, name: &OsStr) -> io::Result<Self> {
+        let path = self.path.join(name);
+
+        if !fs::metadata(&path)?.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::Other, "Not a directory"));
+        }
+
+        Ok(Self { path })
+    }
+
+    pub fn stat(&self) -> io::Result<Stat> {
+        stat_path(&self.path, false)
+    }
+
+    pub fn stat_at(&self, name: &OsStr) -> io::Result<Stat> {
+        stat_path(&self.path.join(name), false)
+    }
+
+    pub fn stat_following_at(&self, name: &OsStr) -> io::Result<Stat> {
+        stat_path(&self.path.join(name), true)
+    }
+
+    /// Only the read-only attribute to go by here
+    #[must_use]
+    pub fn writable_at(&self, name: &OsStr) -> bool {
+        fs::symlink_metadata(self.path.join(name)).is_ok_and(|m| !m.permissions().readonly())
+    }
+
+    pub fn unlink_at(&self, name: &OsStr) -> io::Result<()> {
+        fs::remove_file(self.path.join(name))
+    }
+
+    pub fn remove_dir_at(&self, name: &OsStr) -> io::Result<()> {
+        fs::remove_dir(self.path.join(name))
+    }
+
+    pub fn read_link_at(&self, name: &OsStr) -> io::Result<PathBuf> {
+        fs::read_link(self.path.join(name))
+    }
+
+    pub fn entries(&self) -> io::Result<Vec<OsString>> {
+        fs::read_dir(&self.path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect()
+    }
+}
+
+/// Metadata of `path` along with its volume serial number and file index, what stands in for
+/// device and inode numbers here
+#[cfg(windows)]
+fn stat_path(path: &Path, follow: bool) -> io::Result<Stat> {
+    let metadata = if follow {
+        fs::metadata(path)?
+    } else {
+        fs::symlink_metadata(path)?
+    };
+    let (dev, ino) = file_id(path, follow)?;
+
+    Ok(Stat {
+        dev,
+        ino,
+        ..Stat::from(metadata)
+    })
+}
+
+#[cfg(windows)]
+fn file_id(path: &Path, follow: bool) -> io::Result<(u64, u64)> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    /// `FILE_FLAG_BACKUP_SEMANTICS`, without it directories can't be opened
+    const BACKUP_SEMANTICS: u32 = 0x0200_0000;
+    /// `FILE_FLAG_OPEN_REPARSE_POINT`, opens a symlink rather than its target
+    const OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+
+    /// `BY_HANDLE_FILE_INFORMATION`
+    #[repr(C)]
+    #[derive(Default)]
+    struct FileInformation {
+        attributes: u32,
+        creation_time: [u32; 2],
+        last_access_time: [u32; 2],
+        last_write_time: [u32; 2],
+        volume_serial_number: u32,
+        size_high: u32,
+        size_low: u32,
+        links: u32,
+        index_high: u32,
+        index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(file: RawHandle, information: *mut FileInformation) -> i32;
+    }
+
+    let flags = if follow {
+        BACKUP_SEMANTICS
+    } else {
+        BACKUP_SEMANTICS | OPEN_REPARSE_POINT
+    };
+    // Neither read nor write access is needed to query it
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(flags)
+        .open(path)?;
+
+    let mut information = FileInformation::default();
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut information) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok((
+        u64::from(information.volume_serial_number),
+        u64::from(information.index_high) << 32 | u64::from(information.index_low),
+    ))
+}
+
+/// Everything but the device and inode numbers, `stat_path` fills those in
+#[cfg(windows)]
+impl From<fs::Metadata> for Stat {
+    fn from(metadata: fs::Metadata) -> Self {
+        let kind = match metadata.file_type() {
+            t if t.is_symlink() => Kind::Symlink,
+            t if t.is_dir() => Kind::Dir,
+            t if t.is_file() => Kind::File,
+            _ => Kind::Other,
+        };
+
+        Self {
+            kind,
+            mode: if metadata.permissions().readonly() {
+                0o555
+            } else {
+                0o777
+            },
+            uid: 0,
+            size: metadata.len(),
+            dev: 0,
+            ino: 0,
+        }
+    }
+}
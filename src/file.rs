@@ -1,49 +1,43 @@
-use std::fs;
-
 use crate::arg::InteractiveMode;
-use crate::core::{concat_relative_root, is_write_protected, RmStatus, BIN_NAME};
-use crate::interact;
+use crate::core::{concat_relative_root, RmStatus, BIN_NAME};
+use crate::fd::Stat;
+use crate::interact::Prompts;
 
 #[must_use]
 pub fn prompt(
-    metadata: &fs::Metadata,
+    stat: &Stat,
+    write_protected: bool,
     name: &str,
     rel_root: &str,
     mode: InteractiveMode,
+    prompts: &Prompts,
 ) -> RmStatus {
-    let write_protected = is_write_protected(metadata);
-    let empty = metadata.len() == 0;
-
-    let message = format!(
-        "{bin}: remove{write_protected}regular{empty}file '{relative_name}'?",
-        bin = BIN_NAME,
-        write_protected = if write_protected {
-            " write-protected "
-        } else {
-            " "
-        },
-        empty = if empty { " empty " } else { " " },
-        relative_name = concat_relative_root(rel_root, name)
-    );
+    let empty = stat.size == 0;
 
-    let maybe_interact = match mode {
-        InteractiveMode::Always => interact::with_message(message),
-        InteractiveMode::Once => Ok(true),
-        InteractiveMode::Never => {
-            if write_protected {
-                interact::with_message(message)
-            } else {
-                Ok(true)
-            }
+    let ask = match mode {
+        InteractiveMode::Always => true,
+        InteractiveMode::Once | InteractiveMode::WriteProtected => {
+            write_protected && prompts.asks_write_protected()
         }
+        InteractiveMode::Never => false,
     };
 
-    if let Ok(yes) = maybe_interact {
-        if yes {
-            return RmStatus::Accept;
-        }
-        return RmStatus::Declined;
+    if !ask {
+        return RmStatus::Accept;
     }
 
-    RmStatus::Failed(maybe_interact.unwrap_err())
+    // Formatting is skipped unless asking, the relative name grows with the depth of the tree
+    prompts.ask(|| {
+        format!(
+            "{bin}: remove{write_protected}regular{empty}file '{relative_name}'?",
+            bin = BIN_NAME,
+            write_protected = if write_protected {
+                " write-protected "
+            } else {
+                " "
+            },
+            empty = if empty { " empty " } else { " " },
+            relative_name = concat_relative_root(rel_root, name)
+        )
+    })
 }
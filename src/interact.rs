@@ -1,23 +1,379 @@
-use dialoguer::{theme, Confirm};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::IsTerminal;
+use std::str::FromStr;
 
+#[cfg(unix)]
+use std::fs::OpenOptions;
+
+use dialoguer::console::Term;
+use dialoguer::{theme, Confirm, Input, MultiSelect};
+
+use crate::arg::RmOptions;
+use crate::core::RmStatus;
+use crate::error::Error;
+use crate::progress::{Progress, Skip};
 use crate::Result;
 
+/// What can be answered when asked about a single entry
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// Yes to this one and everything after it, without asking again
+    All,
+    /// No to this one, and leave the rest of its directory alone
+    Skip,
+    /// No to this one, and stop removing anything
+    Quit,
+}
+
+impl FromStr for Answer {
+    type Err = &'static str;
+
+    fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(Self::Yes),
+            "n" | "no" => Ok(Self::No),
+            "a" | "all" => Ok(Self::All),
+            "s" | "skip" => Ok(Self::Skip),
+            "q" | "quit" => Ok(Self::Quit),
+            _ => Err("answer y, n, a, s or q"),
+        }
+    }
+}
+
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Yes => "y",
+            Self::No => "n",
+            Self::All => "a",
+            Self::Skip => "s",
+            Self::Quit => "q",
+        })
+    }
+}
+
+/// Where answers to prompts come from
+///
 /// # Errors
 ///
-/// Fails with I/O error if can't write to stdout
-#[cfg(not(feature = "auto-interactive"))]
-pub fn with_message(message: String) -> Result<bool> {
-    Confirm::with_theme(&theme::SimpleTheme)
-        .with_prompt(message)
-        .default(true)
-        .show_default(true)
-        .interact()
-        .map_err(std::convert::Into::into)
-}
-
-#[cfg(feature = "auto-interactive")]
-#[allow(clippy::needless_pass_by_value)]
-pub fn with_message(message: String) -> Result<bool> {
-    println!("{}", message);
-    Ok(true)
+/// Every prompt fails when no answer can be had, e.g. without a terminal to ask on
+pub trait Prompter {
+    /// A yes or no question
+    fn confirm(&self, message: String) -> Result<bool>;
+
+    /// A question about a single entry, with every `Answer` to choose from
+    fn choose(&self, message: String) -> Result<Answer>;
+
+    /// Indices of the `items` checked, in order
+    fn select(&self, message: String, items: &[String]) -> Result<Vec<usize>>;
+
+    /// A question only typing out `phrase` says yes to, there's no default
+    fn confirm_typed(&self, message: String, phrase: &str) -> Result<bool>;
+}
+
+/// Asks on the controlling terminal. Like GNU `rm`, only a yes removes anything, a bare enter is
+/// a no
+pub struct Terminal;
+
+impl Prompter for Terminal {
+    fn confirm(&self, message: String) -> Result<bool> {
+        Confirm::with_theme(&theme::SimpleTheme)
+            .with_prompt(message)
+            .default(false)
+            .show_default(true)
+            .interact_on(&terminal()?)
+            .map_err(std::convert::Into::into)
+    }
+
+    fn choose(&self, message: String) -> Result<Answer> {
+        Input::with_theme(&theme::SimpleTheme)
+            .with_prompt(message + " [y]es, [n]o, [a]ll, [s]kip directory, [q]uit")
+            .default(Answer::No)
+            .show_default(true)
+            .interact_text_on(&terminal()?)
+            .map_err(std::convert::Into::into)
+    }
+
+    fn select(&self, message: String, items: &[String]) -> Result<Vec<usize>> {
+        MultiSelect::with_theme(&theme::SimpleTheme)
+            .with_prompt(message)
+            .items(items)
+            .interact_on(&terminal()?)
+            .map_err(std::convert::Into::into)
+    }
+
+    fn confirm_typed(&self, message: String, phrase: &str) -> Result<bool> {
+        let answer: String = Input::with_theme(&theme::SimpleTheme)
+            .with_prompt(format!("{message}, type '{phrase}' to go ahead"))
+            .allow_empty(true)
+            .interact_text_on(&terminal()?)?;
+        Ok(answer.trim() == phrase)
+    }
+}
+
+/// Where prompts are shown and answered. Going through the controlling terminal rather than
+/// stdin and stderr keeps prompting possible while those are redirected
+#[cfg(unix)]
+fn terminal() -> Result<Term> {
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .and_then(|tty| Ok((tty.try_clone()?, tty)));
+
+    match tty {
+        Ok((read, write)) => Ok(Term::read_write_pair(read, write)),
+        Err(_) => Err(Error::NoTerminal),
+    }
+}
+
+#[cfg(windows)]
+fn terminal() -> Result<Term> {
+    let term = Term::stderr();
+    if term.is_term() {
+        Ok(term)
+    } else {
+        Err(Error::NoTerminal)
+    }
+}
+
+/// `--assume-yes` or `--assume-no`, prints every prompt along with the answer. A yes checks every
+/// item of a selection and a no none
+pub struct Assume(pub bool);
+
+impl Prompter for Assume {
+    fn confirm(&self, message: String) -> Result<bool> {
+        println!("{message} {}", if self.0 { "y" } else { "n" });
+        Ok(self.0)
+    }
+
+    fn choose(&self, message: String) -> Result<Answer> {
+        let answer = if self.0 { Answer::Yes } else { Answer::No };
+        println!("{message} {answer}");
+        Ok(answer)
+    }
+
+    fn select(&self, message: String, items: &[String]) -> Result<Vec<usize>> {
+        println!("{message}");
+        for item in items {
+            println!("{item}");
+        }
+        Ok(if self.0 {
+            (0..items.len()).collect()
+        } else {
+            Vec::new()
+        })
+    }
+
+    fn confirm_typed(&self, message: String, _phrase: &str) -> Result<bool> {
+        Ok(refuse_typed(&message))
+    }
+}
+
+/// What can only be confirmed by typing it out on the terminal is never assumed nor scripted
+fn refuse_typed(message: &str) -> bool {
+    println!("{message} n, only typing it out on a terminal says yes");
+    false
+}
+
+/// `--answers` or `$RMX_ANSWERS`, one answer per prompt in order, printed along with it
+pub struct Script {
+    answers: RefCell<VecDeque<String>>,
+}
+
+impl Script {
+    pub fn new(answers: &[String]) -> Self {
+        Self {
+            answers: RefCell::new(answers.iter().cloned().collect()),
+        }
+    }
+
+    fn next(&self, message: &str) -> Result<String> {
+        let answer = self.pop()?;
+        println!("{message} {answer}");
+        Ok(answer)
+    }
+
+    fn pop(&self) -> Result<String> {
+        self.answers
+            .borrow_mut()
+            .pop_front()
+            .ok_or(Error::AnswersExhausted)
+    }
+}
+
+impl Prompter for Script {
+    fn confirm(&self, message: String) -> Result<bool> {
+        let answer = self.next(&message)?;
+        match answer.parse() {
+            Ok(Answer::Yes) => Ok(true),
+            Ok(Answer::No) => Ok(false),
+            _ => Err(Error::InvalidAnswer(answer)),
+        }
+    }
+
+    fn choose(&self, message: String) -> Result<Answer> {
+        let answer = self.next(&message)?;
+        answer.parse().map_err(|_| Error::InvalidAnswer(answer))
+    }
+
+    /// `y` checks every item and `n` none, otherwise the indices to check separated by spaces
+    fn select(&self, message: String, items: &[String]) -> Result<Vec<usize>> {
+        println!("{message}");
+        for item in items {
+            println!("{item}");
+        }
+
+        // The message is followed by the items, so the answer goes on a line of its own
+        let answer = self.pop()?;
+        println!("{answer}");
+        match answer.parse() {
+            Ok(Answer::Yes) => return Ok((0..items.len()).collect()),
+            Ok(Answer::No) => return Ok(Vec::new()),
+            _ => (),
+        }
+
+        let mut chosen = answer
+            .split_whitespace()
+            .map(|index| index.parse().ok().filter(|&index| index < items.len()))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or_else(|| Error::InvalidAnswer(answer.clone()))?;
+        chosen.sort_unstable();
+        chosen.dedup();
+        Ok(chosen)
+    }
+
+    /// Doesn't take up an answer
+    fn confirm_typed(&self, message: String, _phrase: &str) -> Result<bool> {
+        Ok(refuse_typed(&message))
+    }
+}
+
+/// Every prompt of a run goes through here, along with the answers that outlast the prompt they
+/// were given at
+pub struct Prompts {
+    prompter: Box<dyn Prompter>,
+    /// Kept from drawing over the prompts
+    progress: Progress,
+    all: Cell<bool>,
+    quit: Cell<bool>,
+    /// Whether write-protected entries are asked about without `-i`
+    write_protected: bool,
+}
+
+impl Prompts {
+    /// Answers from `--answers` or `$RMX_ANSWERS`, then `--assume-yes` or `--assume-no`, or else
+    /// the terminal. Like GNU `rm`, the terminal is only asked about write-protected entries when
+    /// stdin is one too
+    pub fn new(opt: &RmOptions, progress: Progress) -> Self {
+        let prompter: Box<dyn Prompter> = match (&opt.answers, opt.assume) {
+            (Some(answers), _) => Box::new(Script::new(answers)),
+            (None, Some(yes)) => Box::new(Assume(yes)),
+            (None, None) => Box::new(Terminal),
+        };
+        let write_protected =
+            opt.answers.is_some() || opt.assume.is_some() || std::io::stdin().is_terminal();
+
+        Self {
+            write_protected,
+            ..Self::with_prompter(prompter, progress)
+        }
+    }
+
+    pub fn with_prompter(prompter: Box<dyn Prompter>, progress: Progress) -> Self {
+        Self {
+            prompter,
+            progress,
+            all: Cell::new(false),
+            quit: Cell::new(false),
+            write_protected: true,
+        }
+    }
+
+    /// Whether being write-protected is reason enough to ask about an entry
+    pub const fn asks_write_protected(&self) -> bool {
+        self.write_protected
+    }
+
+    /// Ask about an entry unless an earlier answer already settled it. The message is only
+    /// formatted when asking
+    pub fn ask(&self, message: impl FnOnce() -> String) -> RmStatus {
+        if self.quit.get() {
+            return RmStatus::Declined;
+        }
+
+        if self.all.get() {
+            return RmStatus::Accept;
+        }
+
+        let status = match self.progress.suspend(|| self.prompter.choose(message())) {
+            Ok(Answer::Yes) => RmStatus::Accept,
+            Ok(Answer::No) => RmStatus::Declined,
+            Ok(Answer::All) => {
+                self.all.set(true);
+                RmStatus::Accept
+            }
+            Ok(Answer::Skip) => RmStatus::Skipped,
+            Ok(Answer::Quit) => {
+                self.quit.set(true);
+                RmStatus::Declined
+            }
+            Err(err) => {
+                self.quit.set(true);
+                RmStatus::Failed(err)
+            }
+        };
+
+        if matches!(status, RmStatus::Declined | RmStatus::Skipped) {
+            self.progress.skipped(Skip::Declined);
+        }
+
+        status
+    }
+
+    /// A yes or no question that isn't about removing an entry, earlier answers don't settle it
+    ///
+    /// # Errors
+    ///
+    /// Fails when no answer can be had
+    pub fn confirm(&self, message: String) -> Result<bool> {
+        let answer = self.progress.suspend(|| self.prompter.confirm(message));
+        self.quit.set(self.quit.get() || answer.is_err());
+        answer
+    }
+
+    /// A question too consequential for `--assume-yes`, `--answers` or an earlier all to settle,
+    /// only `phrase` typed out on the terminal says yes
+    ///
+    /// # Errors
+    ///
+    /// Fails when no answer can be had
+    pub fn confirm_typed(&self, message: String, phrase: &str) -> Result<bool> {
+        let answer = self
+            .progress
+            .suspend(|| self.prompter.confirm_typed(message, phrase));
+        self.quit.set(self.quit.get() || answer.is_err());
+        answer
+    }
+
+    /// # Errors
+    ///
+    /// Fails when no answer can be had
+    pub fn select(&self, message: String, items: &[String]) -> Result<Vec<usize>> {
+        let chosen = self
+            .progress
+            .suspend(|| self.prompter.select(message, items));
+        self.quit.set(self.quit.get() || chosen.is_err());
+        chosen
+    }
+
+    /// Whether the user asked to stop or no answer could be had, nothing is to be removed from
+    /// then on
+    pub const fn quit(&self) -> bool {
+        self.quit.get()
+    }
 }
@@ -1,50 +1,129 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
 #![allow(clippy::multiple_crate_versions)]
 
-use crate::arg::{interact_level, rm_options, InteractiveMode, RmOptions};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::arg::{rm_options, InteractiveMode, RmOptions};
 use crate::core::{Result, BIN_NAME};
 use error::Error;
+use interact::Prompts;
+use policy::Protected;
+use progress::{Progress, Skip};
+use scan::{Usage, SCAN_TIMEOUT};
 
 mod arg;
 mod core;
+#[cfg(unix)]
+mod device;
 mod dir;
 mod error;
+mod fd;
 mod file;
 mod interact;
 mod link;
+mod pick;
+mod policy;
+mod progress;
+mod scan;
 mod shred;
+#[cfg(unix)]
+mod special;
 mod traverse;
 
 fn main() {
-    if let Err(err) = run() {
-        println!("{}", err);
+    let args = rm_options().get_matches();
+    let opt = RmOptions::from(&args);
+    let progress = Progress::new(&opt);
+
+    let result = run(&opt, &progress);
+    match &result {
+        // What was left behind got counted as it was reported
+        Ok(()) | Err(Error::Failed) => (),
+        Err(err @ Error::Incomplete(_)) => println!("{err}"),
+        Err(err) => {
+            println!("{err}");
+            progress.error();
+        }
+    }
+
+    if opt.summary {
+        progress.summary(&opt);
+    }
+
+    if result.is_err() {
+        std::process::exit(1);
     }
 }
 
-fn run() -> Result<()> {
-    let args = rm_options().get_matches();
-    let opt = RmOptions::from(&args);
+fn run(opt: &RmOptions, progress: &Progress) -> Result<()> {
+    let Some(dir) = &opt.wipe_free_space else {
+        return remove(opt, progress);
+    };
+
+    // The space is only worth wiping once what took it up is gone, and not at all when some of it
+    // couldn't be removed
+    if !opt.file.is_empty() {
+        remove(opt, progress)?;
+    }
+
+    core::wipe_free_space(dir, opt)
+}
+
+fn remove(opt: &RmOptions, progress: &Progress) -> Result<()> {
+    let protected = Protected::load(opt);
 
     // Rip mode
     if opt.rip {
+        let _ticker = progress.start(&opt.file);
+        let mut failed = 0;
         for path in &opt.file {
-            traverse::walk(path)?;
+            failed += traverse::walk(path, opt, &protected, progress);
+        }
+
+        if failed > 0 {
+            return Err(Error::Incomplete(failed));
         }
 
         return Ok(());
     }
 
-    if opt == RmOptions::default() && !opt.force {
+    if opt.file.is_empty() && !opt.force {
         return Err(Error::Usage);
     }
 
-    let mode = interact_level(&opt, &args);
-    if mode == InteractiveMode::Once && (opt.file.len() > 3 || opt.recursive) {
+    let prompts = Prompts::new(opt, progress.clone());
+
+    let picked;
+    let operands = if opt.pick {
+        picked = pick::select(opt, &prompts)?;
+        if picked.is_empty() {
+            return Ok(());
+        }
+        &picked
+    } else {
+        &opt.file
+    };
+
+    let mode = opt.interactive;
+    if mode == InteractiveMode::Once && (operands.len() > 3 || opt.recursive) {
+        // What's below the operands, only worth scanning when removing recursively
+        let usage = if opt.recursive {
+            let deadline = Instant::now() + SCAN_TIMEOUT;
+            let mut usage = Usage::default();
+            for path in operands {
+                usage.add(Usage::scan(Path::new(path), Some(deadline)));
+            }
+            format!(" ({usage})")
+        } else {
+            String::new()
+        };
+
         let message = format!(
-            "{bin}: remove {count} {arguments}{recursive}?",
+            "{bin}: remove {count} {arguments}{recursive}{usage}?",
             bin = BIN_NAME,
-            count = opt.file.len(),
-            arguments = if opt.file.len() == 1 {
+            count = operands.len(),
+            arguments = if operands.len() == 1 {
                 "argument"
             } else {
                 "arguments"
@@ -52,15 +131,40 @@ fn run() -> Result<()> {
             recursive = if opt.recursive { " recursively" } else { "" }
         );
 
-        match interact::with_message(message) {
+        match prompts.confirm(message) {
             Ok(true) => (),
             Err(err) => return Err(err),
-            _ => return Ok(()),
+            _ => {
+                for _ in operands {
+                    progress.skipped(Skip::Declined);
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    let ticker = progress.start(operands);
+    let mut failed = 0;
+    for path in operands {
+        if prompts.quit() {
+            break;
+        }
+
+        // Only failing to prompt or to find the way back up a tree ends up here, the other
+        // operands are still removed unless it was the former
+        match traverse::dfs(path, opt, mode, &prompts, &protected, progress) {
+            Ok(count) => failed += count,
+            Err(err) => {
+                println!("{err}");
+                progress.error();
+                failed += 1;
+            }
         }
     }
+    drop(ticker);
 
-    for path in &opt.file {
-        traverse::dfs(path, String::new(), &opt, mode, false, 0)?;
+    if failed > 0 {
+        return Err(Error::Failed);
     }
 
     Ok(())
@@ -0,0 +1,125 @@
+//! `--pick`: a tree of the operands along with their sizes to choose from before anything is
+//! touched. Whatever is chosen is then removed like operands given on the command line.
+
+use std::ffi::OsString;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use crate::arg::RmOptions;
+use crate::core::{Result, BIN_NAME};
+use crate::interact::Prompts;
+use crate::scan::{human_size, Usage};
+
+/// Levels listed below each operand, anything deeper only counts towards the size of the
+/// directory holding it
+const PICK_DEPTH: usize = 3;
+
+/// An entry of the tree, in the order it's shown
+struct Item {
+    path: PathBuf,
+    depth: usize,
+    dir: bool,
+    /// Apparent size, of everything below it for a directory
+    size: u64,
+}
+
+/// Let the user choose among the operands and what's below them, returns what's to be removed.
+/// Operands that can't be inspected are handed back as they are, their removal reports why
+pub fn select(opt: &RmOptions, prompts: &Prompts) -> Result<Vec<OsString>> {
+    let mut items = Vec::new();
+    let mut operands = Vec::new();
+
+    for operand in &opt.file {
+        let path = Path::new(operand);
+        match fs::symlink_metadata(path) {
+            Ok(metadata) => {
+                list(path, &metadata, 0, &mut items);
+            }
+            Err(_) => operands.push(operand.clone()),
+        }
+    }
+
+    if items.is_empty() {
+        return Ok(operands);
+    }
+
+    let labels: Vec<String> = items.iter().map(label).collect();
+    let chosen = prompts.select(
+        format!("{BIN_NAME}: pick what to remove, space to select and enter to confirm"),
+        &labels,
+    )?;
+
+    // Whatever is below a chosen directory goes along with it. The tree is listed depth first,
+    // so those are right after it
+    let mut covered: Option<&Path> = None;
+    for item in chosen.into_iter().map(|index| &items[index]) {
+        if covered.is_some_and(|dir| item.path.starts_with(dir)) {
+            continue;
+        }
+
+        operands.push(item.path.clone().into_os_string());
+        if item.dir {
+            covered = Some(&item.path);
+        }
+    }
+
+    Ok(operands)
+}
+
+/// Add `path` and whatever is below it up to `PICK_DEPTH`, returns its size
+fn list(path: &Path, metadata: &Metadata, depth: usize, items: &mut Vec<Item>) -> u64 {
+    let index = items.len();
+    items.push(Item {
+        path: path.to_owned(),
+        depth,
+        dir: metadata.is_dir(),
+        size: metadata.len(),
+    });
+
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    let mut size = 0;
+    for entry in sorted_entries(path) {
+        let Ok(metadata) = fs::symlink_metadata(&entry) else {
+            continue;
+        };
+
+        size += if depth < PICK_DEPTH {
+            list(&entry, &metadata, depth + 1, items)
+        } else {
+            Usage::scan(&entry, None).bytes
+        };
+    }
+
+    items[index].size = size;
+    size
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Operands are shown as given, the entries below them by name and indented
+fn label(item: &Item) -> String {
+    let name = if item.depth == 0 {
+        item.path.as_os_str()
+    } else {
+        item.path.file_name().unwrap_or(item.path.as_os_str())
+    };
+
+    format!(
+        "{indent}{name}{slash} ({size})",
+        indent = "  ".repeat(item.depth),
+        name = name.to_string_lossy(),
+        slash = if item.dir { "/" } else { "" },
+        size = human_size(item.size)
+    )
+}
@@ -0,0 +1,254 @@
+//! Paths `rmx` refuses to remove or descend into: `--protect` arguments along with the glob
+//! patterns listed in the system and per-user policy files, one per line. Patterns are expanded
+//! once at startup, so checking an entry doesn't get slower the deeper it is.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+use globset::{Glob, GlobMatcher};
+
+use crate::arg::RmOptions;
+use crate::core::BIN_NAME;
+use crate::fd::Stat;
+
+#[cfg(unix)]
+pub const SYSTEM_POLICY: &str = "/etc/rmx/protect";
+
+/// Everything that's protected, along with what protects it as shown to the user
+#[derive(Debug, Default)]
+pub struct Protected {
+    #[cfg(unix)]
+    entries: HashMap<(u64, u64), String>,
+    /// No inode numbers to go by here
+    #[cfg(windows)]
+    entries: Vec<(PathBuf, String)>,
+}
+
+impl Protected {
+    /// Collect `--protect` paths and the matches of every policy file pattern
+    pub fn load(opt: &RmOptions) -> Self {
+        let mut protected = Self::default();
+
+        for path in &opt.protect {
+            protected.insert(Path::new(path), path.to_string_lossy().into_owned());
+        }
+
+        for file in policy_files() {
+            let Ok(contents) = fs::read_to_string(&file) else {
+                continue;
+            };
+
+            for line in contents.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                match expand(line) {
+                    Ok(paths) => {
+                        for path in paths {
+                            protected.insert(&path, line.to_owned());
+                        }
+                    }
+                    Err(cause) => println!(
+                        "{BIN_NAME}: ignoring '{line}' in '{}': {cause}",
+                        file.display()
+                    ),
+                }
+            }
+        }
+
+        protected
+    }
+
+    /// A symlink protects the link itself rather than its target, like operands it's inspected
+    /// with lstat
+    #[cfg(unix)]
+    fn insert(&mut self, path: &Path, label: String) {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            self.entries
+                .entry((metadata.dev(), metadata.ino()))
+                .or_insert(label);
+        }
+    }
+
+    #[cfg(windows)]
+    fn insert(&mut self, path: &Path, label: String) {
+        if let Some(path) = resolve_parent(path).filter(|path| path.symlink_metadata().is_ok()) {
+            self.entries.push((path, label));
+        }
+    }
+
+    /// What protects `path`, `stat` being the entry as it was inspected
+    #[cfg(unix)]
+    pub fn find(&self, _path: &Path, stat: &Stat) -> Option<&str> {
+        self.entries.get(&(stat.dev, stat.ino)).map(String::as_str)
+    }
+
+    #[cfg(windows)]
+    pub fn find(&self, path: &Path, _stat: &Stat) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let path = resolve_parent(path)?;
+        self.entries
+            .iter()
+            .find(|(protected, _)| *protected == path)
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// What protects a directory `path` is in, however far up. Its components are resolved, so
+    /// going through a symlink doesn't get around it
+    pub fn find_above(&self, path: &Path) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let parent = parent.canonicalize().ok()?;
+
+        parent.ancestors().find_map(|dir| self.find_dir(dir))
+    }
+
+    #[cfg(unix)]
+    fn find_dir(&self, dir: &Path) -> Option<&str> {
+        let metadata = fs::metadata(dir).ok()?;
+        self.entries
+            .get(&(metadata.dev(), metadata.ino()))
+            .map(String::as_str)
+    }
+
+    #[cfg(windows)]
+    fn find_dir(&self, dir: &Path) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(protected, _)| protected == dir)
+            .map(|(_, label)| label.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// `path` with every component but the last one resolved, so a symlink stands for itself
+#[cfg(windows)]
+fn resolve_parent(path: &Path) -> Option<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            Some(parent.canonicalize().ok()?.join(name))
+        }
+        (_, Some(name)) => Some(Path::new(".").canonicalize().ok()?.join(name)),
+        _ => path.canonicalize().ok(),
+    }
+}
+
+/// The system wide policy, then the one of the current user
+fn policy_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    #[cfg(unix)]
+    files.push(PathBuf::from(SYSTEM_POLICY));
+
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home().map(|home| home.join(".config")));
+
+    if let Some(config) = config {
+        files.push(config.join("rmx").join("protect"));
+    }
+
+    files
+}
+
+fn home() -> Option<PathBuf> {
+    #[cfg(unix)]
+    let home = std::env::var_os("HOME");
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+
+    home.filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+/// Existing paths matching `pattern`, matched one component at a time
+fn expand(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let pattern = expand_home(pattern)?;
+
+    if !pattern.is_absolute() {
+        return Err("patterns must be absolute or start with '~'".to_owned());
+    }
+
+    let mut paths = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let Component::Normal(name) = component else {
+            // The root, or `.` and `..` which are taken literally
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        };
+
+        let Some(matcher) = matcher(name)? else {
+            for path in &mut paths {
+                path.push(name);
+            }
+            continue;
+        };
+
+        paths = paths
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|entry| matcher.is_match(entry.file_name()))
+            .map(|entry| entry.path())
+            .collect();
+    }
+
+    Ok(paths)
+}
+
+/// `~` and `$HOME` at the start of a pattern stand for the home directory
+fn expand_home(pattern: &str) -> Result<PathBuf, String> {
+    let rest = ["~", "$HOME"].iter().find_map(|prefix| {
+        pattern
+            .strip_prefix(prefix)
+            .filter(|rest| rest.is_empty() || rest.starts_with(['/', '\\']))
+    });
+
+    match rest {
+        Some(rest) => {
+            let home = home().ok_or_else(|| "home directory is unknown".to_owned())?;
+            Ok(home.join(rest.trim_start_matches(['/', '\\'])))
+        }
+        None => Ok(PathBuf::from(pattern)),
+    }
+}
+
+/// Matcher for a single path component, `None` if it has no wildcards
+fn matcher(name: &OsStr) -> Result<Option<GlobMatcher>, String> {
+    let name = name.to_string_lossy();
+
+    if name.contains("**") {
+        return Err("'**' is not supported".to_owned());
+    }
+
+    if !name.contains(['*', '?', '[', '{']) {
+        return Ok(None);
+    }
+
+    Glob::new(&name)
+        .map(|glob| Some(glob.compile_matcher()))
+        .map_err(|err| err.kind().to_string())
+}
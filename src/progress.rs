@@ -0,0 +1,283 @@
+//! What a run got through: `--progress` keeps a status line on stderr up to date while removing,
+//! `--summary` prints the totals once done. What's below the operands is counted alongside the
+//! removal, the time left is estimated once that's done
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use dialoguer::console::Term;
+
+use crate::arg::RmOptions;
+use crate::fd::Kind;
+use crate::scan::{human_size, thousands, Usage};
+
+/// How often the status line is redrawn
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Why an entry was left in place without it being an error
+#[derive(Debug, Clone, Copy)]
+pub enum Skip {
+    /// `--preserve-root`, along with `.` and `..` operands
+    PreserveRoot,
+    /// `--one-file-system`
+    OtherDevice,
+    /// Answered no to a prompt
+    Declined,
+    /// `--protect`, a policy file, or a link target outside the tree
+    Filtered,
+}
+
+/// Where removals are counted, shared by every thread removing and the one drawing. Does nothing
+/// without `--summary`, nor without `--progress` when stderr isn't a terminal
+#[derive(Clone, Default)]
+pub struct Progress {
+    shared: Option<Arc<Shared>>,
+}
+
+struct Shared {
+    files: AtomicU64,
+    dirs: AtomicU64,
+    symlinks: AtomicU64,
+    bytes: AtomicU64,
+    /// By `Skip`, in order
+    skipped: [AtomicU64; 4],
+    errors: AtomicU64,
+    /// Entries below the operands, the operands included, once counted
+    total: OnceLock<u64>,
+    started: Instant,
+    /// When removal began, after any prompt up front
+    removing: OnceLock<Instant>,
+    /// `--progress` on a terminal
+    draw: bool,
+    /// Held while drawing, and while prompting so nothing is drawn over the prompt
+    line: Mutex<()>,
+    done: AtomicBool,
+}
+
+impl Progress {
+    pub fn new(opt: &RmOptions) -> Self {
+        let draw = opt.progress && Term::stderr().is_term();
+        if !draw && !opt.summary {
+            return Self::default();
+        }
+
+        Self {
+            shared: Some(Arc::new(Shared {
+                files: AtomicU64::new(0),
+                dirs: AtomicU64::new(0),
+                symlinks: AtomicU64::new(0),
+                bytes: AtomicU64::new(0),
+                skipped: Default::default(),
+                errors: AtomicU64::new(0),
+                total: OnceLock::new(),
+                started: Instant::now(),
+                removing: OnceLock::new(),
+                draw,
+                line: Mutex::new(()),
+                done: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    /// Whether anything is counted, the size of what's removed is only worth looking up then
+    pub const fn enabled(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    /// An entry of `bytes` is gone, directories and anything but files count as empty
+    pub fn removed(&self, kind: Kind, bytes: u64) {
+        if let Some(shared) = &self.shared {
+            let count = match kind {
+                Kind::Dir => &shared.dirs,
+                Kind::Symlink => &shared.symlinks,
+                _ => &shared.files,
+            };
+            count.fetch_add(1, Ordering::Relaxed);
+            shared.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    pub fn skipped(&self, skip: Skip) {
+        if let Some(shared) = &self.shared {
+            shared.skipped[skip as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// An error was reported
+    pub fn error(&self) {
+        if let Some(shared) = &self.shared {
+            shared.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Start counting what's below `operands` and drawing, until the returned `Ticker` is dropped
+    pub fn start(&self, operands: &[OsString]) -> Ticker {
+        let Some(shared) = &self.shared else {
+            return Ticker { drawer: None };
+        };
+        shared.removing.get_or_init(Instant::now);
+
+        if !shared.draw {
+            return Ticker { drawer: None };
+        }
+
+        // Left to finish on its own, whatever it hasn't counted by the end of the run doesn't matter
+        let operands = operands.to_vec();
+        let counted = Arc::clone(shared);
+        thread::spawn(move || {
+            let mut usage = Usage::default();
+            for operand in &operands {
+                usage.add(Usage::scan(Path::new(operand), None));
+            }
+            let _ = counted.total.set(usage.files + usage.dirs);
+        });
+
+        let drawn = Arc::clone(shared);
+        let drawer = thread::spawn(move || {
+            let term = Term::stderr();
+            while !drawn.done.load(Ordering::Relaxed) {
+                thread::park_timeout(REDRAW_INTERVAL);
+
+                let _line = drawn.line.lock().unwrap_or_else(PoisonError::into_inner);
+                if drawn.done.load(Ordering::Relaxed) {
+                    break;
+                }
+                let _ = term
+                    .clear_line()
+                    .and_then(|()| term.write_str(&drawn.status()));
+            }
+            let _ = term.clear_line();
+        });
+
+        Ticker {
+            drawer: Some((Arc::clone(shared), drawer)),
+        }
+    }
+
+    /// Run `prompt` with the status line cleared and kept from being drawn
+    pub fn suspend<T>(&self, prompt: impl FnOnce() -> T) -> T {
+        let Some(shared) = self.shared.as_ref().filter(|shared| shared.draw) else {
+            return prompt();
+        };
+
+        let _line = shared.line.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = Term::stderr().clear_line();
+        prompt()
+    }
+
+    /// `--summary`, the totals of the run worded after how entries were removed
+    pub fn summary(&self, opt: &RmOptions) {
+        let Some(shared) = &self.shared else {
+            return;
+        };
+        let load = |count: &AtomicU64| count.load(Ordering::Relaxed);
+
+        let (removed, freed) = if opt.dry_run {
+            ("would remove", "would free")
+        } else if opt.trash {
+            ("trashed", "in trash")
+        } else {
+            ("removed", "freed")
+        };
+        let skipped: Vec<u64> = shared.skipped.iter().map(load).collect();
+
+        println!(
+            "{removed}: {}, {}, {}",
+            count(load(&shared.files), "file", "files"),
+            count(load(&shared.dirs), "directory", "directories"),
+            count(load(&shared.symlinks), "symlink", "symlinks")
+        );
+        println!("{freed}: {}", human_size(load(&shared.bytes)));
+        println!(
+            "skipped: {} ({} preserve-root, {} other device, {} declined, {} filtered)",
+            thousands(skipped.iter().sum()),
+            thousands(skipped[Skip::PreserveRoot as usize]),
+            thousands(skipped[Skip::OtherDevice as usize]),
+            thousands(skipped[Skip::Declined as usize]),
+            thousands(skipped[Skip::Filtered as usize])
+        );
+        println!("errors: {}", thousands(load(&shared.errors)));
+        println!("elapsed: {}", elapsed(shared.started.elapsed()));
+    }
+}
+
+impl Shared {
+    /// e.g. `removed 12,340 of 50,000 entries, 3.2 GiB freed, 4,100 entries/s, 0:09 left`
+    fn status(&self) -> String {
+        let entries = self.files.load(Ordering::Relaxed)
+            + self.dirs.load(Ordering::Relaxed)
+            + self.symlinks.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let elapsed = self
+            .removing
+            .get()
+            .map_or(0, |removing| removing.elapsed().as_millis())
+            .max(1);
+        let rate = u128::from(entries) * 1000 / elapsed;
+
+        let total = self.total.get().copied();
+
+        format!(
+            "removed {entries}{of_total} entries, {size} freed, {rate} entries/s{left}",
+            of_total = total.map_or_else(String::new, |total| format!(
+                " of {}",
+                thousands(total.max(entries))
+            )),
+            entries = thousands(entries),
+            size = human_size(bytes),
+            rate = thousands(u64::try_from(rate).unwrap_or(u64::MAX)),
+            left = match total {
+                Some(total) if entries > 0 => {
+                    let left =
+                        u128::from(total.saturating_sub(entries)) * elapsed / u128::from(entries);
+                    format!(", {} left", clock(left / 1000))
+                }
+                _ => String::new(),
+            }
+        )
+    }
+}
+
+/// Draws the status line until dropped, then clears it
+pub struct Ticker {
+    drawer: Option<(Arc<Shared>, JoinHandle<()>)>,
+}
+
+impl Drop for Ticker {
+    fn drop(&mut self) {
+        if let Some((shared, drawer)) = self.drawer.take() {
+            shared.done.store(true, Ordering::Relaxed);
+            drawer.thread().unpark();
+            let _ = drawer.join();
+        }
+    }
+}
+
+/// e.g. `1 file` or `12,340 files`
+fn count(n: u64, one: &str, many: &str) -> String {
+    format!("{} {}", thousands(n), if n == 1 { one } else { many })
+}
+
+/// Seconds as `m:ss`, or `h:mm:ss` past an hour
+fn clock(secs: u128) -> String {
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{secs:02}")
+    } else {
+        format!("{minutes}:{secs:02}")
+    }
+}
+
+/// Tenths of a second under a minute, e.g. `4.2s`, otherwise like `clock`
+fn elapsed(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis < 60_000 {
+        format!("{}.{}s", millis / 1000, millis / 100 % 10)
+    } else {
+        clock(millis / 1000)
+    }
+}
@@ -0,0 +1,120 @@
+//! A quick look at what's below a directory, so prompts can tell how much is about to go
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a prompt waits on a scan, anything not counted by then is left out
+pub const SCAN_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Files and bytes below a directory, directories only count towards `dirs`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Usage {
+    pub files: u64,
+    /// Directories, the one scanned included
+    pub dirs: u64,
+    pub bytes: u64,
+    /// The deadline passed before everything was counted
+    pub partial: bool,
+}
+
+impl Usage {
+    /// Everything below `path`, or `path` itself if it isn't a directory. Symlinks aren't
+    /// followed, and the scan gives up once `deadline` passes
+    pub fn scan(path: &Path, deadline: Option<Instant>) -> Self {
+        let mut usage = Self::default();
+
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => (),
+            Ok(metadata) => {
+                usage.files = 1;
+                usage.bytes = metadata.len();
+                return usage;
+            }
+            Err(_) => return usage,
+        }
+
+        let mut dirs = vec![PathBuf::from(path)];
+
+        while let Some(dir) = dirs.pop() {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                usage.partial = true;
+                break;
+            }
+
+            usage.dirs += 1;
+
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in entries.filter_map(std::result::Result::ok) {
+                match entry.metadata() {
+                    Ok(metadata) if metadata.is_dir() => dirs.push(entry.path()),
+                    Ok(metadata) => {
+                        usage.files += 1;
+                        usage.bytes += metadata.len();
+                    }
+                    Err(_) => (),
+                }
+            }
+        }
+
+        usage
+    }
+
+    pub const fn add(&mut self, other: Self) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.bytes += other.bytes;
+        self.partial |= other.partial;
+    }
+}
+
+/// e.g. `12,340 files, 3.2 GiB`, prefixed with `at least` when the scan gave up early
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{at_least}{files} {noun}, {size}",
+            at_least = if self.partial { "at least " } else { "" },
+            files = thousands(self.files),
+            noun = if self.files == 1 { "file" } else { "files" },
+            size = human_size(self.bytes)
+        )
+    }
+}
+
+/// Sizes in binary units with one decimal, e.g. `1.5 KiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+    let mut tenths = u128::from(bytes) * 10;
+    let mut unit = 0;
+    while tenths >= 10 * 1024 && unit < UNITS.len() - 1 {
+        tenths /= 1024;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{}.{} {}", tenths / 10, tenths % 10, UNITS[unit])
+    }
+}
+
+/// Digits grouped by three, e.g. `12,340`
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    grouped
+}
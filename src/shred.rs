@@ -1,8 +1,16 @@
 use std::borrow::ToOwned;
 use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::ops::Range;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::iter;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::ffi::CString;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::os::unix::ffi::OsStrExt;
 
 use rand::Rng;
 use zeroize::Zeroize;
@@ -10,27 +18,186 @@ use zeroize::Zeroize;
 use crate::Result;
 
 const DEFAULT_SHRED_ITERATIONS: usize = 1000;
+const DEVICE_SHRED_PASSES: usize = 3;
+/// Random names tried for each length when the all zeroes name is taken
+const WIPE_NAME_ATTEMPTS: usize = 10;
+const CHUNK_SIZE: usize = 1 << 20;
+/// Some file systems can't hold huge files, so free space is filled with many of these
+const MAX_FILLER_SIZE: u64 = 1 << 30;
 
 pub struct Shredder {
     path: PathBuf,
     name: OsString,
-    size_range: Range<usize>,
+    size: usize,
+    size_range: RangeInclusive<usize>,
 }
 
 #[allow(clippy::cast_possible_truncation)]
 pub fn shred(path: &OsStr) -> Result<()> {
     let size = fs::metadata(path)?.len() as usize;
-    if size == 0 {
-        fs::remove_file(&path)?;
-        return Ok(());
-    }
 
     Shredder::new(path, size).run()
 }
 
+/// Overwrite a block device in place with random data. The device node itself is kept
+#[allow(clippy::cast_possible_truncation)]
+pub fn shred_device(path: &OsStr) -> Result<()> {
+    let mut device = fs::OpenOptions::new().write(true).open(path)?;
+    let size = device.seek(SeekFrom::End(0))?;
+    let mut bytes = vec![0_u8; CHUNK_SIZE];
+
+    for _ in 0..DEVICE_SHRED_PASSES {
+        device.seek(SeekFrom::Start(0))?;
+        let mut remaining = size;
+
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_SIZE as u64) as usize;
+            rand::thread_rng().fill(&mut bytes[..len]);
+            device.write_all(&bytes[..len])?;
+            remaining -= len as u64;
+        }
+
+        device.sync_all()?;
+    }
+
+    bytes.zeroize();
+
+    Ok(())
+}
+
+/// Fill the file system holding `dir` with random data until it runs out of space, then release
+/// it. Returns the amount of bytes written
+pub fn wipe_free_space(dir: &OsStr) -> io::Result<u64> {
+    let mut fillers = Vec::new();
+    let outcome = fill_free_space(Path::new(dir), &mut fillers);
+
+    // Every filler is released even when one can't be, then the first error is reported
+    let mut removed = Ok(());
+    for filler in &fillers {
+        if let Err(err) = fs::remove_file(filler) {
+            removed = removed.and(Err(err));
+        }
+    }
+
+    outcome.and_then(|written| removed.map(|()| written))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn fill_free_space(dir: &Path, fillers: &mut Vec<PathBuf>) -> io::Result<u64> {
+    let mut bytes = vec![0_u8; CHUNK_SIZE];
+    let mut written = 0;
+
+    loop {
+        let path = dir.join(format!(".{}", random_name(30)));
+        let mut filler = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(filler) => filler,
+            Err(err) if is_out_of_space(&err) => break,
+            Err(err) => return Err(err),
+        };
+        fillers.push(path);
+
+        let mut size = 0;
+        let out_of_space = loop {
+            if size >= MAX_FILLER_SIZE {
+                break false;
+            }
+
+            rand::thread_rng().fill(&mut bytes[..]);
+            match filler.write(&bytes) {
+                Ok(0) => break true,
+                Ok(len) => size += len as u64,
+                Err(err) if err.kind() == io::ErrorKind::FileTooLarge => break false,
+                Err(err) if is_out_of_space(&err) => break true,
+                Err(err) => return Err(err),
+            }
+        };
+
+        // Delayed allocation may only notice the disk is full here
+        match filler.sync_all() {
+            Err(err) if !is_out_of_space(&err) => return Err(err),
+            _ => written += size,
+        }
+
+        if out_of_space {
+            break;
+        }
+    }
+
+    bytes.zeroize();
+
+    Ok(written)
+}
+
+fn is_out_of_space(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+/// Make a rename durable, otherwise the old directory entry may still be what hits the disk
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod xattr {
+    use std::ffi::CStr;
+
+    use libc::{c_char, ssize_t};
+
+    pub unsafe fn list(path: &CStr, names: *mut c_char, size: usize) -> ssize_t {
+        libc::llistxattr(path.as_ptr(), names, size)
+    }
+
+    pub unsafe fn remove(path: &CStr, name: &CStr) -> i32 {
+        libc::lremovexattr(path.as_ptr(), name.as_ptr())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod xattr {
+    use std::ffi::CStr;
+
+    use libc::{c_char, ssize_t, XATTR_NOFOLLOW};
+
+    pub unsafe fn list(path: &CStr, names: *mut c_char, size: usize) -> ssize_t {
+        libc::listxattr(path.as_ptr(), names, size, XATTR_NOFOLLOW)
+    }
+
+    pub unsafe fn remove(path: &CStr, name: &CStr) -> i32 {
+        libc::removexattr(path.as_ptr(), name.as_ptr(), XATTR_NOFOLLOW)
+    }
+}
+
+fn random_name(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
 impl Shredder {
     pub fn new(path: &OsStr, size: usize) -> Self {
-        let size_range = (size - size / 2)..(size + size / 2);
+        let size_range = (size - size / 2)..=(size + size / 2);
         let name = Path::new(path)
             .file_name()
             .map(ToOwned::to_owned)
@@ -39,11 +206,23 @@ impl Shredder {
         Self {
             path: PathBuf::from(path),
             name,
+            size,
             size_range,
         }
     }
 
     fn run(&mut self) -> Result<()> {
+        if self.size > 0 {
+            self.overwrite()?;
+        }
+
+        self.scrub()?;
+        fs::remove_file(&self.path)?;
+
+        Ok(())
+    }
+
+    fn overwrite(&mut self) -> Result<()> {
         let instruction: usize = rand::thread_rng().gen_range(1..=3000);
 
         for _ in 1..=DEFAULT_SHRED_ITERATIONS {
@@ -55,19 +234,110 @@ impl Shredder {
             }
         }
 
-        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    /// Leave as little as possible behind once the contents are gone: no size, no extended
+    /// attributes, no timestamps and no trace of the original name length
+    fn scrub(&mut self) -> Result<()> {
+        let file = fs::OpenOptions::new().write(true).open(&self.path)?;
+        file.set_len(0)?;
+        file.sync_all()?;
+
+        self.strip_xattrs()?;
+
+        // Truncating bumps the modification time, so this goes last
+        let epoch = fs::FileTimes::new()
+            .set_accessed(UNIX_EPOCH)
+            .set_modified(UNIX_EPOCH);
+        file.set_times(epoch)?;
+        file.sync_all()?;
+        drop(file);
+
+        self.wipe_name()
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[allow(clippy::cast_sign_loss)]
+    fn strip_xattrs(&self) -> Result<()> {
+        let path = CString::new(self.path.as_os_str().as_bytes()).map_err(io::Error::from)?;
+
+        let size = unsafe { xattr::list(&path, std::ptr::null_mut(), 0) };
+        if size <= 0 {
+            // Either there are none or the file system doesn't support them
+            return Ok(());
+        }
+
+        let mut names = vec![0_u8; size as usize];
+        let size = unsafe { xattr::list(&path, names.as_mut_ptr().cast(), names.len()) };
+        if size < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        for name in names[..size as usize].split(|&b| b == 0) {
+            if name.is_empty() {
+                continue;
+            }
+
+            let name = CString::new(name).map_err(io::Error::from)?;
+            if unsafe { xattr::remove(&path, &name) } == -1 {
+                let err = io::Error::last_os_error();
+
+                // Namespaces like `security.*` are not ours to strip
+                if !matches!(
+                    err.raw_os_error(),
+                    Some(libc::EPERM | libc::EACCES | libc::ENOTSUP)
+                ) {
+                    return Err(err.into());
+                }
+            }
+        }
 
         Ok(())
     }
 
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    #[allow(clippy::unused_self, clippy::unnecessary_wraps)]
+    fn strip_xattrs(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Rename to shorter and shorter names before unlinking, like GNU `shred -u`
+    fn wipe_name(&mut self) -> Result<()> {
+        let parent = self.parent();
+
+        for len in (1..=self.name.len()).rev() {
+            let candidates = iter::once("0".repeat(len))
+                .chain(iter::repeat_with(|| random_name(len)).take(WIPE_NAME_ATTEMPTS));
+
+            for name in candidates {
+                let path = parent.join(&name);
+                if path.symlink_metadata().is_ok() {
+                    continue;
+                }
+
+                fs::rename(&self.path, &path)?;
+                sync_dir(&parent)?;
+                self.path = path;
+                self.name = OsString::from(name);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parent(&self) -> PathBuf {
+        self.path
+            .parent()
+            .map(ToOwned::to_owned)
+            .unwrap_or_default()
+    }
+
     fn noise(&self) -> Result<()> {
         let size: usize = rand::thread_rng().gen_range(self.size_range.clone());
         let mut bytes: Vec<u8> = (1..=size).map(|_| rand::random::<u8>()).collect();
-        let name: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(30)
-            .map(char::from)
-            .collect();
+        let name = random_name(30);
 
         fs::write(&name, &bytes)?;
         fs::remove_file(&name)?;
@@ -89,20 +359,8 @@ impl Shredder {
     }
 
     fn rename(&mut self) -> Result<()> {
-        let name: OsString = OsString::from(
-            rand::thread_rng()
-                .sample_iter(&rand::distributions::Alphanumeric)
-                .take(30)
-                .map(char::from)
-                .collect::<String>(),
-        );
-        let parent = self
-            .path
-            .clone()
-            .parent()
-            .map(ToOwned::to_owned)
-            .unwrap_or_default();
-        let path = parent.join(&name);
+        let name = OsString::from(random_name(30));
+        let path = self.parent().join(&name);
 
         fs::rename(&self.path, &path)?;
         self.path = path;
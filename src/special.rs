@@ -0,0 +1,39 @@
+use crate::arg::InteractiveMode;
+use crate::core::{concat_relative_root, RmStatus, BIN_NAME};
+use crate::interact::Prompts;
+
+/// Prompt for a FIFO, socket or device node, `kind` being its type as shown to the user
+#[must_use]
+pub fn prompt(
+    write_protected: bool,
+    kind: &str,
+    name: &str,
+    rel_root: &str,
+    mode: InteractiveMode,
+    prompts: &Prompts,
+) -> RmStatus {
+    let ask = match mode {
+        InteractiveMode::Always => true,
+        InteractiveMode::Once | InteractiveMode::WriteProtected => {
+            write_protected && prompts.asks_write_protected()
+        }
+        InteractiveMode::Never => false,
+    };
+
+    if !ask {
+        return RmStatus::Accept;
+    }
+
+    prompts.ask(|| {
+        format!(
+            "{bin}: remove {write_protected}{kind} '{relative_name}'?",
+            bin = BIN_NAME,
+            write_protected = if write_protected {
+                "write-protected "
+            } else {
+                ""
+            },
+            relative_name = concat_relative_root(rel_root, name)
+        )
+    })
+}
@@ -1,164 +1,1043 @@
-use std::collections::BTreeMap;
-use std::ffi::OsStr;
-use std::fs;
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use std::{fs, io};
 
 #[cfg(unix)]
 use std::ffi::CString;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
 
 use crossbeam_channel::unbounded;
 use crossbeam_channel::{Receiver, Sender};
 
-use crate::arg::{InteractiveMode, RmOptions};
+use crate::arg::{FollowLinks, InteractiveMode, RmOptions};
 use crate::core::{
-    concat_relative_root, fs_entity, one_file_system, preserve_root, unlink_dir, unlink_file,
-    unlink_symlink, FsEntity, Result, RmStatus,
+    self, concat_relative_root, dot_or_dotdot, fs_entity, is_write_protected, one_file_system,
+    preserve_all_root, preserve_root, restore_holder, unlink_dir, unlink_file, unlink_symlink,
+    FsEntity, Holder, Location, Result, RmStatus,
 };
+use crate::error::Error;
+use crate::fd::{Dir, Kind, Stat};
+use crate::interact::Prompts;
+use crate::policy::Protected;
+use crate::progress::{Progress, Skip};
+use crate::scan::{Usage, SCAN_TIMEOUT};
 use crate::{dir, file, link};
 
+#[cfg(unix)]
+use crate::core::{other_device, shred_block_device, unlink_special};
+#[cfg(unix)]
+use crate::{device, special};
+
+/// Directories deeper than this in the stack give up their descriptor and get reopened through
+/// `..` on the way back up, so depth isn't bounded by the open files limit
+const MAX_OPEN_DIRS: usize = 64;
+
+/// Remove the command line operand `path`, descending into it with directory descriptors.
+/// Returns how many entries were reported and left behind, removal goes on past those. Only
+/// failing to prompt or to find the way back up the tree stops it
 pub fn dfs(
     path: &OsStr,
-    rel_root: String,
     opt: &RmOptions,
     mode: InteractiveMode,
-    visited: bool,
-    parent_inode_id: u64,
-) -> Result<()> {
-    let ent = fs_entity(path);
+    prompts: &Prompts,
+    protected: &Protected,
+    progress: &Progress,
+) -> Result<usize> {
+    if core::protected_above(protected, Path::new(path), "", &path.to_string_lossy()) {
+        progress.skipped(Skip::Filtered);
+        return Ok(1);
+    }
 
-    if let Err(err) = ent {
-        println!("{}", err);
-        return Ok(());
+    let operand = Dir::cwd().stat_at(path).ok();
+
+    if let Some(stat) = operand.filter(|stat| stat.kind == Kind::Dir) {
+        if (opt.recursive || opt.dir)
+            && (dot_or_dotdot(path) || preserve_all_root(opt, path, &stat))
+        {
+            progress.skipped(Skip::PreserveRoot);
+            return Ok(1);
+        }
     }
 
-    match ent? {
-        FsEntity::File {
-            metadata,
+    let mut walk = Dfs {
+        opt,
+        mode,
+        prompts,
+        protected,
+        progress,
+        path: PathBuf::from(path),
+        rel_root: String::new(),
+        active: HashSet::new(),
+        root: operand.filter(|stat| stat.kind == Kind::Dir),
+        root_dev: operand.map_or(0, |stat| stat.dev),
+        holder: Cell::default(),
+        failed: 0,
+    };
+
+    let outcome = walk.visit(&Dir::cwd(), path, false);
+    restore_holder(&Dir::cwd(), walk.holder.get());
+    outcome?;
+
+    Ok(walk.failed)
+}
+
+struct Dfs<'a> {
+    opt: &'a RmOptions,
+    mode: InteractiveMode,
+    prompts: &'a Prompts,
+    protected: &'a Protected,
+    progress: &'a Progress,
+    /// Full path of the entry being visited, only for the APIs that can't work with descriptors
+    path: PathBuf,
+    /// Relative name of the directory holding the entry being visited, as shown to the user
+    rel_root: String,
+    /// `(dev, ino)` of the directories being traversed and the symlinks being followed, only
+    /// tracked with `--follow-links` since a tree can't loop back on itself otherwise
+    active: HashSet<(u64, u64)>,
+    /// The operand if it's a directory, `--follow-links=inside` keeps to targets below it
+    root: Option<Stat>,
+    /// Device of the operand, what `--one-file-system` keeps to
+    root_dev: u64,
+    /// Attributes of the directory whose entries are being visited
+    holder: Cell<Holder>,
+    failed: usize,
+}
+
+/// What became of an entry
+enum Step {
+    Removed,
+    /// Left in place, so are the directories above it
+    Kept,
+    /// Left in place along with whatever is left of the directory holding it
+    Skipped,
+    Descend(Frame),
+}
+
+/// A directory whose entries are being removed
+struct Frame {
+    name: OsString,
+    display: String,
+    stat: Stat,
+    /// Entries still to visit
+    entries: Vec<OsString>,
+    /// Closed for all but the innermost `MAX_OPEN_DIRS` frames
+    dir: Option<Dir>,
+    /// Length of `rel_root` before descending into this directory
+    rel_len: usize,
+    /// Some entry was left in place, so this directory can't be removed either
+    kept: bool,
+    /// `holder` of the directory above, set aside while visiting this one
+    above: Holder,
+}
+
+impl Dfs<'_> {
+    fn at<'a>(&'a self, parent: &'a Dir, name: &'a OsStr) -> Location<'a> {
+        Location {
+            parent,
             name,
-            inode_id,
-        } => match file::prompt(&metadata, &name, &rel_root, mode) {
-            RmStatus::Accept => {
-                if one_file_system(opt, &name, parent_inode_id, inode_id) {
-                    return Ok(());
+            path: &self.path,
+            holder: Some(&self.holder),
+        }
+    }
+
+    /// Remove `name` and everything below it. Directories are kept on an explicit stack rather
+    /// than recursed into, so arbitrarily deep trees neither overflow the stack nor need paths
+    /// longer than `PATH_MAX`
+    fn visit(&mut self, parent: &Dir, name: &OsStr, listed: bool) -> Result<Step> {
+        let root = match self.enter(parent, name, listed)? {
+            Step::Descend(frame) => frame,
+            step => return Ok(step),
+        };
+        let mut stack = vec![root];
+
+        while let Some(top) = stack.last_mut() {
+            if let Some(entry) = top.entries.pop() {
+                let dir = top.dir.as_ref().expect("innermost directory is open");
+                self.path.push(&entry);
+
+                match self.enter(dir, &entry, true) {
+                    Ok(Step::Descend(frame)) => {
+                        let depth = stack.len();
+                        if depth >= MAX_OPEN_DIRS {
+                            stack[depth - MAX_OPEN_DIRS].dir = None;
+                        }
+                        stack.push(frame);
+                    }
+                    Ok(Step::Skipped) => {
+                        top.entries.clear();
+                        top.kept = true;
+                        self.path.pop();
+                    }
+                    Ok(step) => {
+                        top.kept |= matches!(step, Step::Kept);
+                        self.path.pop();
+                    }
+                    Err(err) => {
+                        self.path.pop();
+                        return Err(err);
+                    }
                 }
 
-                unlink_file(path, &name, &rel_root, opt)?;
+                continue;
             }
-            RmStatus::Declined => return Ok(()),
-            RmStatus::Failed(err) => return Err(err),
-        },
 
-        FsEntity::Dir {
-            metadata,
-            name,
-            inode_id,
-        } => {
-            match dir::prompt(opt, path, &rel_root, &metadata, &name, mode, visited) {
+            let frame = stack.pop().expect("stack is not empty");
+            self.rel_root.truncate(frame.rel_len);
+            self.active.remove(&(frame.stat.dev, frame.stat.ino));
+            let dir = frame.dir.as_ref().expect("innermost directory is open");
+            let holder = self.holder.replace(frame.above);
+
+            let parent = match stack.last_mut() {
+                Some(above) => {
+                    if above.dir.is_none() {
+                        above.dir = Some(reopen_parent(dir, above)?);
+                    }
+                    above.dir.as_ref().expect("just reopened")
+                }
+                None => parent,
+            };
+
+            let step = if frame.kept {
+                Step::Kept
+            } else {
+                self.leave(parent, &frame)?
+            };
+            // Only what's staying gets its attributes back
+            if matches!(step, Step::Kept) {
+                restore_holder(dir, holder);
+            }
+
+            match stack.last_mut() {
+                Some(above) => {
+                    above.kept |= matches!(step, Step::Kept);
+                    self.path.pop();
+                }
+                None => return Ok(step),
+            }
+        }
+
+        unreachable!("the operand is the last directory left")
+    }
+
+    /// Handle a single entry. Anything but a directory with entries left to remove is dealt with
+    /// right away, those are returned for the caller to descend into instead. `listed` entries
+    /// come from reading their directory
+    fn enter(&mut self, parent: &Dir, name: &OsStr, listed: bool) -> Result<Step> {
+        let opt = self.opt;
+
+        // The user asked to stop, leave everything that's left in place
+        if self.prompts.quit() {
+            return Ok(Step::Kept);
+        }
+
+        let ent = match fs_entity(&self.at(parent, name)) {
+            Ok(ent) => ent,
+            // Already removed through a followed link since its directory was read
+            Err(Error::NoSuchFile(_)) if listed && opt.follow_symlinks.enabled() => {
+                return Ok(Step::Removed)
+            }
+            Err(Error::NoSuchFile(_)) if opt.force => return Ok(Step::Removed),
+            Err(err @ Error::NoSuchFile(_)) => {
+                println!("{err}");
+                self.progress.error();
+                self.failed += 1;
+                return Ok(Step::Removed);
+            }
+            Err(err) => return Ok(self.keep(&err)),
+        };
+
+        let rel_root = self.rel_root.as_str();
+
+        if core::protected(self.protected, &self.path, rel_root, ent.name(), ent.stat()) {
+            self.progress.skipped(Skip::Filtered);
+            self.failed += 1;
+            return Ok(Step::Kept);
+        }
+
+        match ent {
+            FsEntity::File {
+                stat,
+                name: display,
+            } => match file::prompt(
+                &stat,
+                is_write_protected(&self.at(parent, name), &stat),
+                &display,
+                rel_root,
+                self.mode,
+                self.prompts,
+            ) {
                 RmStatus::Accept => {
-                    if preserve_root(opt, path) {
-                        return Ok(());
+                    if let Err(err) = unlink_file(
+                        &self.at(parent, name),
+                        &stat,
+                        &display,
+                        rel_root,
+                        opt,
+                        self.prompts,
+                        self.progress,
+                    ) {
+                        return Ok(self.keep(&err));
                     }
+                }
+                RmStatus::Declined => return Ok(Step::Kept),
+                RmStatus::Skipped => return Ok(Step::Skipped),
+                RmStatus::Failed(err) => return Err(err),
+            },
+
+            FsEntity::Dir {
+                stat,
+                name: display,
+            } => return self.enter_dir(parent, name, stat, display),
 
-                    if !unlink_dir(path, &name, &rel_root, visited, opt)? {
-                        for entry in fs::read_dir(path)? {
-                            let path = entry?.path();
-                            let rel_root = concat_relative_root(&rel_root, &name);
+            FsEntity::Symlink {
+                stat,
+                name: display,
+            } => return self.enter_symlink(parent, name, &stat, display),
 
-                            if one_file_system(opt, &rel_root, parent_inode_id, inode_id) {
-                                return Ok(());
-                            }
-                            dfs(path.as_os_str(), rel_root, opt, mode, false, inode_id)?;
+            #[cfg(unix)]
+            FsEntity::Fifo { .. } | FsEntity::Socket { .. } | FsEntity::CharDevice { .. } => {
+                return self.enter_special(parent, name, &ent)
+            }
+
+            #[cfg(unix)]
+            FsEntity::BlockDevice { name: display, .. } if opt.shred => {
+                match device::prompt(&display, rel_root, self.prompts) {
+                    RmStatus::Accept => {
+                        let at = self.at(parent, name);
+                        if let Err(err) = shred_block_device(&at, &display, rel_root, opt) {
+                            return Ok(self.keep(&err));
                         }
-                        // Parent folder is deleted last
-                        dfs(path, rel_root, opt, mode, true, inode_id)?;
                     }
+                    RmStatus::Declined | RmStatus::Skipped => {
+                        self.progress.skipped(Skip::Declined);
+                    }
+                    RmStatus::Failed(err) => return Err(err),
                 }
-                RmStatus::Declined => return Ok(()),
-                RmStatus::Failed(err) => return Err(err),
+
+                // The device node itself stays
+                return Ok(Step::Kept);
             }
+
+            #[cfg(unix)]
+            FsEntity::BlockDevice { .. } => return self.enter_special(parent, name, &ent),
         }
 
-        FsEntity::Symlink { name, inode_id, .. } => match link::prompt(&name, &rel_root, mode) {
+        Ok(Step::Removed)
+    }
+
+    /// Prompt for and unlink a symlink, along with its target when following links
+    fn enter_symlink(
+        &mut self,
+        parent: &Dir,
+        name: &OsStr,
+        stat: &Stat,
+        display: String,
+    ) -> Result<Step> {
+        let opt = self.opt;
+        let rel_root = self.rel_root.as_str();
+
+        // Already being followed further up, the link leads back to itself
+        if self.active.contains(&(stat.dev, stat.ino)) {
+            let relative_name = concat_relative_root(rel_root, &display);
+            return Ok(self.keep(&Error::SymlinkLoop(relative_name)));
+        }
+
+        // Shown as `link -> target` once the target is going to be removed too
+        let target = if opt.follow_symlinks.enabled() {
+            match parent.read_link_at(name) {
+                Ok(target) => Some(target),
+                Err(err) => {
+                    let relative_name = concat_relative_root(rel_root, &display);
+                    return Ok(self.keep(&Error::from_io(relative_name, err)));
+                }
+            }
+        } else {
+            None
+        };
+        let shown = match &target {
+            Some(target) => format!("{display} -> {}", target.display()),
+            None => display,
+        };
+
+        match link::prompt(&shown, rel_root, self.mode, self.prompts) {
             RmStatus::Accept => {
-                if one_file_system(opt, &name, parent_inode_id, inode_id) {
-                    return Ok(());
+                if let Some(target) = target {
+                    // The target is protected, so is the link leading to it
+                    if !self.follow(parent, name, &target, stat, &shown)? {
+                        return Ok(Step::Kept);
+                    }
                 }
 
-                if opt.follow_symlinks {
-                    let resolved_path = fs::read_link(path)?;
-                    dfs(
-                        resolved_path.as_os_str(),
-                        String::new(),
-                        opt,
-                        mode,
-                        true,
-                        parent_inode_id,
-                    )?;
+                match unlink_symlink(
+                    &self.at(parent, name),
+                    &shown,
+                    &self.rel_root,
+                    opt,
+                    self.prompts,
+                    self.progress,
+                ) {
+                    Ok(()) => Ok(Step::Removed),
+                    Err(err) => Ok(self.keep(&err)),
                 }
+            }
+            RmStatus::Declined => Ok(Step::Kept),
+            RmStatus::Skipped => Ok(Step::Skipped),
+            RmStatus::Failed(err) => Err(err),
+        }
+    }
+
+    /// Prompt for and unlink a FIFO, socket or device node
+    #[cfg(unix)]
+    fn enter_special(&mut self, parent: &Dir, name: &OsStr, ent: &FsEntity) -> Result<Step> {
+        let rel_root = self.rel_root.as_str();
+        let display = ent.name();
+
+        let at = self.at(parent, name);
+        let write_protected = is_write_protected(&at, ent.stat());
+
+        match special::prompt(
+            write_protected,
+            ent.file_type(),
+            display,
+            rel_root,
+            self.mode,
+            self.prompts,
+        ) {
+            RmStatus::Accept => {
+                match unlink_special(
+                    &at,
+                    display,
+                    rel_root,
+                    self.opt,
+                    self.prompts,
+                    self.progress,
+                ) {
+                    Ok(()) => Ok(Step::Removed),
+                    Err(err) => Ok(self.keep(&err)),
+                }
+            }
+            RmStatus::Declined => Ok(Step::Kept),
+            RmStatus::Skipped => Ok(Step::Skipped),
+            RmStatus::Failed(err) => Err(err),
+        }
+    }
+
+    fn enter_dir(
+        &mut self,
+        parent: &Dir,
+        name: &OsStr,
+        stat: Stat,
+        display: String,
+    ) -> Result<Step> {
+        let opt = self.opt;
+        let rel_root = self.rel_root.as_str();
+
+        // A followed symlink led back to a directory that's still being removed
+        if self.active.contains(&(stat.dev, stat.ino)) {
+            let relative_name = concat_relative_root(rel_root, &display);
+            return Ok(self.keep(&Error::DirectoryCycle(relative_name)));
+        }
 
-                unlink_symlink(path, &name, &rel_root, opt)?;
+        // Mount points below the operand are left alone, along with everything above them
+        if one_file_system(opt, rel_root, &display, self.root_dev, stat.dev) {
+            self.progress.skipped(Skip::OtherDevice);
+            self.failed += 1;
+            return Ok(Step::Kept);
+        }
+
+        // Checked ahead of any prompt, there's no point asking about `/`
+        if (opt.recursive || opt.dir) && preserve_root(opt, &self.path, rel_root, &display, &stat) {
+            self.progress.skipped(Skip::PreserveRoot);
+            self.failed += 1;
+            return Ok(Step::Kept);
+        }
+
+        // Without -r or -d this is an error anyway, don't bother opening it
+        let (dir, entries) = if opt.recursive || opt.dir {
+            let opened = open_dir(parent, name, &stat, &display).and_then(|dir| {
+                let entries = dir
+                    .entries()
+                    .map_err(|err| Error::from_io(display.clone(), err))?;
+                Ok((dir, entries))
+            });
+            match opened {
+                Ok((dir, entries)) => (Some(dir), entries),
+                Err(err) => return Ok(self.keep(&err)),
             }
-            RmStatus::Declined => return Ok(()),
+        } else {
+            (None, Vec::new())
+        };
+
+        match dir::prompt(
+            opt,
+            entries.is_empty(),
+            rel_root,
+            is_write_protected(&self.at(parent, name), &stat),
+            &display,
+            self.mode,
+            false,
+            || Usage::scan(&self.path, Some(Instant::now() + SCAN_TIMEOUT)),
+            self.prompts,
+        ) {
+            RmStatus::Accept => (),
+            // Skipping a directory keeps everything in it
+            RmStatus::Declined | RmStatus::Skipped => return Ok(Step::Kept),
             RmStatus::Failed(err) => return Err(err),
-        },
+        }
+
+        if entries.is_empty() {
+            return match unlink_dir(
+                &self.at(parent, name),
+                &stat,
+                &display,
+                rel_root,
+                opt,
+                self.prompts,
+                self.progress,
+            ) {
+                Ok(()) => Ok(Step::Removed),
+                Err(err) => Ok(self.keep(&err)),
+            };
+        }
+
+        let rel_len = self.rel_root.len();
+        if !self.rel_root.is_empty() {
+            self.rel_root.push('/');
+        }
+        self.rel_root.push_str(&display);
+
+        if opt.follow_symlinks.enabled() {
+            self.active.insert((stat.dev, stat.ino));
+        }
+
+        Ok(Step::Descend(Frame {
+            name: name.to_owned(),
+            display,
+            stat,
+            entries,
+            dir,
+            rel_len,
+            kept: false,
+            above: self.holder.take(),
+        }))
     }
 
-    Ok(())
+    /// Remove a directory once everything in it is gone
+    fn leave(&mut self, parent: &Dir, frame: &Frame) -> Result<Step> {
+        let opt = self.opt;
+        let rel_root = self.rel_root.as_str();
+
+        // Parent folder is deleted last
+        match dir::prompt(
+            opt,
+            false,
+            rel_root,
+            is_write_protected(&self.at(parent, &frame.name), &frame.stat),
+            &frame.display,
+            self.mode,
+            true,
+            Usage::default,
+            self.prompts,
+        ) {
+            RmStatus::Accept => match unlink_dir(
+                &self.at(parent, &frame.name),
+                &frame.stat,
+                &frame.display,
+                rel_root,
+                opt,
+                self.prompts,
+                self.progress,
+            ) {
+                Ok(()) => Ok(Step::Removed),
+                Err(err) => Ok(self.keep(&err)),
+            },
+            RmStatus::Declined | RmStatus::Skipped => Ok(Step::Kept),
+            RmStatus::Failed(err) => Err(err),
+        }
+    }
+
+    /// Report an entry that couldn't be removed and carry on with the rest, the directories above
+    /// it are kept
+    fn keep(&mut self, err: &Error) -> Step {
+        println!("{err}");
+        self.progress.error();
+        self.failed += 1;
+        Step::Kept
+    }
+
+    /// Remove whatever the symlink `name` points to, ahead of the link itself. Returns whether the
+    /// link can go too
+    fn follow(
+        &mut self,
+        parent: &Dir,
+        name: &OsStr,
+        target: &Path,
+        stat: &Stat,
+        shown: &str,
+    ) -> Result<bool> {
+        // Relative targets resolve against the directory holding the link, which is only `parent`
+        // itself when `name` has a single component
+        let resolved = Path::new(name)
+            .parent()
+            .map_or_else(|| target.to_owned(), |dir| dir.join(target));
+
+        if self.opt.follow_symlinks == FollowLinks::Inside && !self.leads_inside(parent, &resolved)
+        {
+            let relative_name = concat_relative_root(&self.rel_root, shown);
+            println!("{}", Error::OutsideTree(relative_name));
+            self.progress.skipped(Skip::Filtered);
+            return Ok(true);
+        }
+
+        let target_path = self.path.with_file_name(target);
+        if core::protected_above(self.protected, &target_path, &self.rel_root, shown) {
+            self.progress.skipped(Skip::Filtered);
+            self.failed += 1;
+            return Ok(false);
+        }
+
+        let link = (stat.dev, stat.ino);
+        self.active.insert(link);
+
+        // Whatever is below the target is shown through the link, e.g. `dir/../other/file`
+        let target_rel_root = Path::new(&self.rel_root)
+            .join(target)
+            .parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let link_path = std::mem::replace(&mut self.path, target_path);
+        let rel_root = std::mem::replace(&mut self.rel_root, target_rel_root);
+
+        let outcome = self.visit(parent, resolved.as_os_str(), false);
+
+        self.path = link_path;
+        self.rel_root = rel_root;
+        self.active.remove(&link);
+        outcome.map(|_| true)
+    }
+
+    /// Whether `target`, relative to `parent`, is below the directory operand. A symlink operand
+    /// was named explicitly, so its target always is
+    fn leads_inside(&self, parent: &Dir, target: &Path) -> bool {
+        self.root
+            .as_ref()
+            .is_none_or(|root| is_below(root, parent, target).unwrap_or(false))
+    }
+}
+
+/// Climb from `target` through `..` until reaching `root` or `/`
+fn is_below(root: &Stat, parent: &Dir, target: &Path) -> io::Result<bool> {
+    let mut dir = match parent.open_following(target.as_os_str()) {
+        Ok(dir) => dir,
+        // Anything but a directory is judged by the one holding it
+        Err(err) if err.kind() == io::ErrorKind::NotADirectory => {
+            let holder = target
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            parent.open_following(holder.as_os_str())?
+        }
+        // Dangling or looping, visiting the target reports it without removing anything
+        Err(_) => return Ok(true),
+    };
+    let mut stat = dir.stat()?;
+
+    while !stat.same_inode(root) {
+        let up = dir.open_at(OsStr::new(".."))?;
+        let up_stat = up.stat()?;
+
+        // `/` is its own parent
+        if up_stat.same_inode(&stat) {
+            return Ok(false);
+        }
+
+        (dir, stat) = (up, up_stat);
+    }
+
+    Ok(true)
+}
+
+/// Open a directory for traversal, making sure it's still the one that was inspected
+fn open_dir(parent: &Dir, name: &OsStr, stat: &Stat, display: &str) -> Result<Dir> {
+    let dir = parent
+        .open_at(name)
+        .map_err(|err| Error::from_io(display.to_owned(), err))?;
+
+    if !dir.stat()?.same_inode(stat) {
+        let err = io::Error::other("directory was replaced during removal");
+        return Err(Error::Os(display.to_owned(), err));
+    }
+
+    Ok(dir)
+}
+
+/// Get back to a directory whose descriptor was given up, through `..` of the one below it
+fn reopen_parent(child: &Dir, frame: &Frame) -> Result<Dir> {
+    let dir = child
+        .open_at(OsStr::new(".."))
+        .map_err(|err| Error::from_io(frame.display.clone(), err))?;
+
+    if !dir.stat()?.same_inode(&frame.stat) {
+        let err = io::Error::other("directory was moved during removal");
+        return Err(Error::Os(frame.display.clone(), err));
+    }
+
+    Ok(dir)
+}
+
+/// Files are handed to workers in batches of this size, so wide directories are spread out
+const RIP_BATCH_SIZE: usize = 128;
+
+/// A directory being ripped. It's removed by whichever worker drops its last pending child
+struct RipDir {
+    path: PathBuf,
+    parent: Option<Arc<Self>>,
+    /// Subdirectories and file batches still around, plus one held by the scan of this directory
+    pending: AtomicUsize,
+    /// Something below couldn't be removed, so neither can this
+    kept: AtomicBool,
+}
+
+enum RipJob {
+    Scan(Arc<RipDir>),
+    Unlink(Arc<RipDir>, Vec<PathBuf>),
+    Stop,
+}
+
+struct Rip<'a> {
+    tx: Sender<RipJob>,
+    protected: &'a Protected,
+    progress: &'a Progress,
+    /// Jobs queued or running, the last worker to finish one stops everyone
+    jobs: AtomicUsize,
+    failures: AtomicUsize,
+    threads: usize,
+    root_dev: Option<u64>,
+    verbose: bool,
+    dry_run: bool,
+}
+
+/// Rip through `path`, returns how many entries failed to be removed. Errors are reported as
+/// they happen, and directories above a failure are left alone without further noise
+pub fn walk(path: &OsStr, opt: &RmOptions, protected: &Protected, progress: &Progress) -> usize {
+    let (verbose, dry_run) = (opt.verbose, opt.dry_run);
+    let name = display(Path::new(path));
+
+    let stat = match Dir::cwd().stat_at(path) {
+        Ok(stat) => stat,
+        Err(err) => {
+            println!("{}", Error::from_io(name, err));
+            progress.error();
+            return 1;
+        }
+    };
+
+    if core::protected_above(protected, Path::new(path), "", &name)
+        || core::protected(protected, Path::new(path), "", &name, &stat)
+    {
+        progress.skipped(Skip::Filtered);
+        return 1;
+    }
+
+    // Not walking a symlink operand, even if it points to a directory
+    if stat.kind != Kind::Dir {
+        return usize::from(!rip_file(Path::new(path), verbose, dry_run, progress));
+    }
+
+    if dot_or_dotdot(path)
+        || preserve_root(opt, Path::new(path), "", &name, &stat)
+        || preserve_all_root(opt, path, &stat)
+    {
+        progress.skipped(Skip::PreserveRoot);
+        return 1;
+    }
+
+    #[cfg(unix)]
+    let root_dev = opt.one_file_system.then_some(stat.dev);
+    #[cfg(windows)]
+    let root_dev = None;
+
+    let threads = opt.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
+    let (tx, rx): (Sender<RipJob>, Receiver<RipJob>) = unbounded();
+    let rip = Rip {
+        tx,
+        protected,
+        progress,
+        jobs: AtomicUsize::new(1),
+        failures: AtomicUsize::new(0),
+        threads,
+        root_dev,
+        verbose,
+        dry_run,
+    };
+
+    let root = RipDir {
+        path: PathBuf::from(path),
+        parent: None,
+        pending: AtomicUsize::new(1),
+        kept: AtomicBool::new(false),
+    };
+    rip.send(RipJob::Scan(Arc::new(root)));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| rip.work(&rx));
+        }
+    });
+
+    rip.failures.into_inner()
 }
 
-#[allow(clippy::unnecessary_wraps)]
-#[allow(clippy::while_let_loop)]
-pub fn walk(path: &OsStr) -> Result<()> {
-    let mut dirs: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
-    let (tx, rx): (Sender<PathBuf>, Receiver<PathBuf>) = unbounded();
-
-    let handle = std::thread::spawn(move || loop {
-        match rx.recv() {
-            Ok(path) => {
-                #[cfg(unix)]
-                {
-                    let c_path = unsafe {
-                        CString::new(path.to_str().unwrap_unchecked()).unwrap_unchecked()
-                    };
-                    unsafe { if libc::unlink(c_path.as_ptr()) == -1 {} };
+impl Rip<'_> {
+    fn send(&self, job: RipJob) {
+        self.tx.send(job).expect("receiver to be alive");
+    }
+
+    fn work(&self, rx: &Receiver<RipJob>) {
+        for job in rx {
+            match job {
+                RipJob::Scan(dir) => self.scan(&dir),
+                RipJob::Unlink(dir, files) => {
+                    for file in files {
+                        if !rip_file(&file, self.verbose, self.dry_run, self.progress) {
+                            self.fail(&dir);
+                        }
+                    }
+                    self.release(dir);
                 }
+                RipJob::Stop => break,
+            }
 
-                #[cfg(windows)]
-                fs::remove_file(path).unwrap();
+            if self.jobs.fetch_sub(1, Ordering::AcqRel) == 1 {
+                for _ in 0..self.threads {
+                    self.send(RipJob::Stop);
+                }
             }
-            _ => break,
         }
-    });
+    }
 
-    jwalk::WalkDir::new(path)
-        .skip_hidden(false)
-        .into_iter()
-        .for_each(|t| {
-            let t = unsafe { t.unwrap_unchecked() };
-            let (path, depth) = (t.path(), t.depth);
+    fn scan(&self, dir: &Arc<RipDir>) {
+        let entries = match fs::read_dir(&dir.path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                println!("{}", Error::from_io(display(&dir.path), err));
+                self.progress.error();
+                self.fail(dir);
+                self.release(Arc::clone(dir));
+                return;
+            }
+        };
 
-            if path.is_dir() {
-                dirs.entry(depth).or_insert_with(Vec::new).push(path);
-            } else {
-                unsafe { tx.send(path).unwrap_unchecked() };
+        let mut files = Vec::new();
+
+        for entry in entries {
+            let (path, file_type) = match entry.and_then(|e| Ok((e.path(), e.file_type()?))) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    println!("{}", Error::from_io(display(&dir.path), err));
+                    self.progress.error();
+                    self.fail(dir);
+                    continue;
+                }
+            };
+
+            if self.is_protected(&path) {
+                self.progress.skipped(Skip::Filtered);
+                self.fail(dir);
+                continue;
+            }
+
+            if !file_type.is_dir() {
+                files.push(path);
+                if files.len() == RIP_BATCH_SIZE {
+                    self.spawn(
+                        dir,
+                        RipJob::Unlink(Arc::clone(dir), std::mem::take(&mut files)),
+                    );
+                }
+                continue;
+            }
+
+            if self.other_device(&path) {
+                self.progress.skipped(Skip::OtherDevice);
+                self.fail(dir);
+                continue;
+            }
+
+            let child = RipDir {
+                path,
+                parent: Some(Arc::clone(dir)),
+                pending: AtomicUsize::new(1),
+                kept: AtomicBool::new(false),
+            };
+            self.spawn(dir, RipJob::Scan(Arc::new(child)));
+        }
+
+        if !files.is_empty() {
+            self.spawn(dir, RipJob::Unlink(Arc::clone(dir), files));
+        }
+
+        self.release(Arc::clone(dir));
+    }
+
+    /// Queue a job that holds one of `dir`'s pending slots until it's done
+    fn spawn(&self, dir: &RipDir, job: RipJob) {
+        dir.pending.fetch_add(1, Ordering::AcqRel);
+        self.jobs.fetch_add(1, Ordering::AcqRel);
+        self.send(job);
+    }
+
+    fn is_protected(&self, path: &Path) -> bool {
+        if self.protected.is_empty() {
+            return false;
+        }
+
+        Dir::cwd()
+            .stat_at(path.as_os_str())
+            .is_ok_and(|stat| core::protected(self.protected, path, "", &display(path), &stat))
+    }
+
+    #[cfg(unix)]
+    fn other_device(&self, path: &Path) -> bool {
+        self.root_dev.is_some_and(|root_dev| {
+            fs::symlink_metadata(path)
+                .is_ok_and(|m| other_device(&display(path), root_dev, m.dev()))
+        })
+    }
+
+    #[cfg(windows)]
+    const fn other_device(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn fail(&self, dir: &RipDir) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        dir.kept.store(true, Ordering::Relaxed);
+    }
+
+    /// Drop one pending slot of `dir`, removing it and walking up while that was the last one
+    fn release(&self, mut dir: Arc<RipDir>) {
+        loop {
+            if dir.pending.fetch_sub(1, Ordering::AcqRel) != 1 {
+                return;
             }
-        });
 
-    drop(tx);
-    unsafe { handle.join().unwrap_unchecked() };
+            let Some(parent) = dir.parent.clone() else {
+                self.remove_dir(&dir, None);
+                return;
+            };
+
+            self.remove_dir(&dir, Some(&parent));
+            dir = parent;
+        }
+    }
+
+    fn remove_dir(&self, dir: &RipDir, parent: Option<&RipDir>) {
+        if dir.kept.load(Ordering::Relaxed) {
+            if let Some(parent) = parent {
+                parent.kept.store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        if self.dry_run {
+            println!("would remove directory '{}'", dir.path.display());
+            self.progress.removed(Kind::Dir, 0);
+            return;
+        }
 
-    for (_, dir) in dirs.iter().rev() {
-        #[cfg(unix)]
-        for d in dir {
-            let c_path = unsafe { CString::new(d.to_str().unwrap_unchecked()).unwrap_unchecked() };
-            unsafe { if libc::rmdir(c_path.as_ptr()) == -1 {} };
+        match rmdir(&dir.path) {
+            Ok(()) => {
+                if self.verbose {
+                    println!("directory '{}' was removed", dir.path.display());
+                }
+                self.progress.removed(Kind::Dir, 0);
+            }
+            Err(err) => {
+                println!("{}", Error::from_io(display(&dir.path), err));
+                self.progress.error();
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                if let Some(parent) = parent {
+                    parent.kept.store(true, Ordering::Relaxed);
+                }
+            }
         }
+    }
+}
+
+/// Unlink a non directory entry while ripping, reporting the outcome
+fn rip_file(path: &Path, verbose: bool, dry_run: bool, progress: &Progress) -> bool {
+    // Only looked up for the progress, it's an extra call for every file otherwise
+    let (kind, size) = match progress.enabled().then(|| fs::symlink_metadata(path)) {
+        Some(Ok(metadata)) if metadata.is_symlink() => (Kind::Symlink, 0),
+        Some(Ok(metadata)) if metadata.is_file() => (Kind::File, metadata.len()),
+        _ => (Kind::Other, 0),
+    };
 
-        #[cfg(windows)]
-        for _ in dir {
-            fs::remove_dir(path).unwrap();
+    if dry_run {
+        println!("would remove '{}'", path.display());
+        progress.removed(kind, size);
+        return true;
+    }
+
+    match unlink(path) {
+        Ok(()) => {
+            if verbose {
+                println!("removed '{}'", path.display());
+            }
+            progress.removed(kind, size);
+            true
+        }
+        Err(err) => {
+            println!("{}", Error::from_io(display(path), err));
+            progress.error();
+            false
         }
     }
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(unix)]
+fn unlink(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    if unsafe { libc::unlink(c_path.as_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
 
     Ok(())
 }
+
+#[cfg(unix)]
+fn rmdir(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    if unsafe { libc::rmdir(c_path.as_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn unlink(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn rmdir(path: &Path) -> io::Result<()> {
+    fs::remove_dir(path)
+}
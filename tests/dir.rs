@@ -1,3 +1,6 @@
+// Written before clippy flagged borrowed arrays passed to `args`
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use std::fs;
 
 use assert_cmd::prelude::*;
@@ -6,14 +9,28 @@ use assert_fs::TempDir;
 use escargot::CargoBuild;
 use predicates as pd;
 
-/// Build `rmx` bin that accepts every command line interaction
+/// Build `rmx` bin that answers yes to every prompt
 fn no_interactive_bin() -> std::process::Command {
-    CargoBuild::new()
-        .bin("rmx")
-        .features("auto-interactive")
-        .run()
-        .unwrap()
-        .command()
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+/// Root may write to anything, so GNU `rm` never deems an entry write-protected for it
+fn is_root() -> bool {
+    #[cfg(unix)]
+    return unsafe { libc::geteuid() } == 0;
+    #[cfg(windows)]
+    return false;
+}
+
+/// How prompts describe a read-only entry, depending on who's asking
+fn write_protected() -> &'static str {
+    if is_root() {
+        " "
+    } else {
+        " write-protected "
+    }
 }
 
 #[test]
@@ -75,7 +92,12 @@ fn directory_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(dir.path()).arg("-d").assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains("remove write-protected directory"));
+    }
 }
 
 #[test]
@@ -121,7 +143,10 @@ fn interactive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-d").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -168,7 +193,12 @@ fn recursive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(dir.path()).arg("-r").assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains("remove write-protected directory"));
+    }
 }
 
 #[test]
@@ -182,7 +212,12 @@ fn recursive_remove_write_protected_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(dir.path()).arg("-r").assert();
-    assert.stdout(pd::str::contains("descend into write-protected directory"));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains("descend into write-protected directory"));
+    }
 }
 
 #[test]
@@ -215,7 +250,10 @@ fn recursive_interactive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-r").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -229,7 +267,10 @@ fn recursive_interactive_remove_write_protected_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-r").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("descend into write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "descend into{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -308,7 +349,14 @@ fn force_directory_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-d").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("Operation not permitted"));
+    // Only the directory holding it needs to be writable
+    #[cfg(unix)]
+    {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    }
+    #[cfg(windows)]
+    assert.stdout(pd::str::contains("Permission denied"));
 }
 
 #[test]
@@ -355,7 +403,10 @@ fn force_directory_interactive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-d").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -402,7 +453,14 @@ fn force_recursive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-r").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("Operation not permitted"));
+    // Only the directory holding it needs to be writable
+    #[cfg(unix)]
+    {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    }
+    #[cfg(windows)]
+    assert.stdout(pd::str::contains("Permission denied"));
 }
 
 #[test]
@@ -416,7 +474,12 @@ fn force_recursive_remove_write_protected_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-r").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("Permission denied"));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains("Permission denied"));
+    }
 }
 
 #[test]
@@ -449,7 +512,10 @@ fn force_recursive_interactive_remove_write_protected_empty_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-r").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("remove write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -463,7 +529,10 @@ fn force_recursive_interactive_remove_write_protected_directory() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg("-f").arg("-r").arg("-i").arg(dir.path()).assert();
-    assert.stdout(pd::str::contains("descend into write-protected directory"));
+    assert.stdout(pd::str::contains(format!(
+        "descend into{}directory",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -478,18 +547,775 @@ fn interactive_once_remove_two_empty_directories_recursively() {
         .arg("-r")
         .args(&[dir.path(), dir1.path()])
         .assert();
-    assert.stdout(pd::str::contains("remove 2 arguments recursively?"));
+    assert.stdout(pd::str::contains(
+        "remove 2 arguments recursively (0 files, 0 B)?",
+    ));
 }
 
 #[test]
-fn preserve_root_equals_dir_remove_directory() {
+/// `rmx -Ir dir` where dir holds 1200 files
+fn interactive_once_remove_directory_shows_usage() {
     let dir = TempDir::new().unwrap();
+    for i in 0..1200 {
+        dir.child(format!("nested/{i}")).write_str("ab").unwrap();
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-I").arg("-r").arg(dir.path()).assert();
+    assert.success().stdout(pd::str::contains(
+        "remove 1 argument recursively (1,200 files, 2.3 KiB)?",
+    ));
+    dir.assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -ri dir`
+fn interactive_recursive_remove_directory_shows_usage() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").write_binary(&[0; 3072]).unwrap();
+    dir.child("other").touch().unwrap();
 
     let mut cmd = no_interactive_bin();
     let assert = cmd
-        .arg(format!("--preserve-root={}", dir.path().display()))
+        .current_dir(dir.path())
+        .args(["-r", "-i", "nested", "other"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "rmx: descend into directory 'nested' (1 file, 3.0 KiB)?",
+        ))
+        .stdout(pd::str::contains("rmx: remove directory 'nested'?"));
+}
+
+#[test]
+/// `rmx --protect dir -d dir dir`
+fn protect_dir_remove_directory() {
+    let dir = TempDir::new().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--protect")
+        .arg(dir.path())
         .arg("-d")
         .args(&[dir.path(), dir.path()])
         .assert();
-    assert.stdout(pd::str::contains("refusing to remove"));
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"));
+    dir.assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r --protect dir/nested dir`
+fn protect_nested_dir_recursive_remove_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    dir.child("nested/file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--protect")
+        .arg(dir.path().join("nested"))
+        .arg(dir.path())
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"))
+        .stdout(pd::str::contains("Directory not empty").count(0));
+    dir.child("file").assert(pd::path::missing());
+    dir.child("nested/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r --protect dir dir/nested`
+fn protect_dir_recursive_remove_nested_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--protect")
+        .arg(dir.path())
+        .arg(dir.path().join("nested"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"));
+    dir.child("nested/file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r --protect dir link/nested` where link -> dir
+fn protect_dir_recursive_remove_nested_through_symlink() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    std::os::unix::fs::symlink(dir.path(), other.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--protect")
+        .arg(dir.path())
+        .arg(other.path().join("link/nested"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"));
+    dir.child("nested/file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -rl --protect dir other` where other/link -> dir/nested, the link stays along with its
+/// target
+fn protect_dir_follow_symlink_into_protected() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    std::os::unix::fs::symlink(dir.path().join("nested"), other.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-rl")
+        .arg("--protect")
+        .arg(dir.path())
+        .arg(other.path())
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"))
+        .stdout(pd::str::contains("removed").count(0));
+    dir.child("nested/file").assert(pd::path::exists());
+    other.child("link").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r --protect other/link dir other` where other/link -> dir, the link is protected rather
+/// than its target
+fn protect_symlink_recursive_remove_target() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    std::os::unix::fs::symlink(dir.path(), other.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--protect")
+        .arg(other.path().join("link"))
+        .arg(dir.path())
+        .arg(other.path())
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove").count(1));
+    dir.assert(pd::path::missing());
+    assert!(other.path().join("link").symlink_metadata().is_ok());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r dir` where the user policy file protects dir/keep*
+fn policy_glob_recursive_remove_directory() {
+    let dir = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    dir.child("keep_me/file").touch().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    config
+        .child("rmx/protect")
+        .write_str(&format!("# comment\n\n{}/keep*\n", dir.path().display()))
+        .unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .env("XDG_CONFIG_HOME", config.path())
+        .arg("-r")
+        .arg(dir.path())
+        .assert();
+    assert.failure().stdout(pd::str::contains(format!(
+        "keep_me': skipping (protected '{}/keep*')",
+        dir.path().display()
+    )));
+    dir.child("keep_me/file").assert(pd::path::exists());
+    dir.child("file").assert(pd::path::missing());
+    dir.child("nested").assert(pd::path::missing());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -d dir` where the user policy file protects ~/dir
+fn policy_home_remove_directory() {
+    let home = TempDir::new().unwrap();
+    home.child("dir").create_dir_all().unwrap();
+    home.child(".config/rmx/protect")
+        .write_str("~/dir\n")
+        .unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .env_remove("XDG_CONFIG_HOME")
+        .env("HOME", home.path())
+        .arg("-d")
+        .arg(home.path().join("dir"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("(protected '~/dir')"));
+    home.child("dir").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -d dir` where the user policy file holds a relative pattern
+fn policy_ignores_relative_pattern() {
+    let dir = TempDir::new().unwrap();
+    let config = TempDir::new().unwrap();
+    config.child("rmx/protect").write_str("dir\n").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .env("XDG_CONFIG_HOME", config.path())
+        .arg("-d")
+        .arg(dir.path())
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("ignoring 'dir' in"));
+    dir.assert(pd::path::missing());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -rl --dry-run dir` where dir/link -> /
+fn preserve_root_followed_link_to_root() {
+    let dir = TempDir::new().unwrap();
+    std::os::unix::fs::symlink("/", dir.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-rl").arg("--dry-run").arg(dir.path()).assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains(
+            "rmx: it is dangerous to operate recursively on '/'",
+        ))
+        .stdout(pd::str::contains(
+            "rmx: use --no-preserve-root to override this failsafe",
+        ));
+}
+
+#[test]
+/// `rmx -r .`
+fn recursive_remove_dot_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.current_dir(dir.path()).arg("-r").arg(".").assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: refusing to remove '.' or '..' directory: skipping '.'",
+    ));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r nested/..`
+fn recursive_remove_dotdot_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .arg("-r")
+        .arg("nested/..")
+        .assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: refusing to remove '.' or '..' directory: skipping 'nested/..'",
+    ));
+    dir.child("nested/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -d ./ nested/..// nested`
+fn directory_remove_trailing_dot_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested").create_dir_all().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-d", "./", "nested/..//", "nested"])
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("skipping './'"))
+        .stdout(pd::str::contains("skipping 'nested/..//'"));
+    dir.child("nested").assert(pd::path::missing());
+    dir.assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip dir/.`
+fn rip_remove_dot_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg(dir.path().join(".")).assert();
+    assert.failure().stdout(pd::str::contains(
+        "refusing to remove '.' or '..' directory",
+    ));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r --preserve-root=dir dir`
+fn preserve_root_only_accepts_all() {
+    let dir = TempDir::new().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg(format!("--preserve-root={}", dir.path().display()))
+        .arg(dir.path())
+        .assert();
+    assert
+        .failure()
+        .stderr(pd::str::contains("isn't a valid value"));
+    dir.assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r --dry-run dir`
+fn recursive_dry_run_remove_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-r").arg("--dry-run").arg(dir.path()).assert();
+    assert
+        .stdout(pd::str::contains("would remove '"))
+        .stdout(pd::str::contains("would remove directory"));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -r --progress dir` with stderr redirected, nothing is drawn
+fn recursive_progress_without_terminal() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").write_str("hello").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-r").arg("--progress").arg(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::is_empty())
+        .stderr(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx -r --progress --dry-run dir`
+fn recursive_progress_conflicts_with_dry_run() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--progress")
+        .arg("--dry-run")
+        .arg(dir.path())
+        .assert();
+    assert.failure();
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r --summary dir missing`
+fn recursive_summary_remove_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").write_binary(&[0; 1536]).unwrap();
+    dir.child("empty").touch().unwrap();
+    std::os::unix::fs::symlink("empty", dir.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--summary")
+        .arg(dir.path())
+        .arg(dir.path().join("missing"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains(
+            "removed: 2 files, 2 directories, 1 symlink",
+        ))
+        .stdout(pd::str::contains("freed: 1.5 KiB"))
+        .stdout(pd::str::contains(
+            "skipped: 0 (0 preserve-root, 0 other device, 0 declined, 0 filtered)",
+        ))
+        .stdout(pd::str::contains("errors: 1"))
+        .stdout(pd::str::contains("elapsed: "));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx -r --summary --dry-run dir`
+fn recursive_summary_dry_run() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").write_str("hello").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--summary")
+        .arg("--dry-run")
+        .arg(dir.path())
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "would remove: 1 file, 1 directory, 0 symlinks",
+        ))
+        .stdout(pd::str::contains("would free: 5 B"));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r dir` where dir holds a symlink to a directory elsewhere
+fn recursive_remove_directory_keeps_symlink_target() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    other.child("file").touch().unwrap();
+    dir.child("nested").create_dir_all().unwrap();
+    std::os::unix::fs::symlink(other.path(), dir.path().join("nested/link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-r").arg(dir.path()).assert();
+    assert.stdout(pd::str::is_empty());
+    assert!(!dir.path().exists());
+    other.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r dir` where dir holds names that aren't valid UTF-8
+fn recursive_remove_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = TempDir::new().unwrap();
+    let name = OsStr::from_bytes(b"\xff\xfe");
+    fs::create_dir(dir.path().join(name)).unwrap();
+    fs::write(dir.path().join(name).join(name), b"").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-r").arg(dir.path()).assert();
+    assert.stdout(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+/// Nest `depth` directories in `root`, descending through descriptors since the full path
+/// outgrows `PATH_MAX` long before the bottom
+#[cfg(unix)]
+fn deep_nested_dir(root: &std::path::Path, depth: usize) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let root = CString::new(root.as_os_str().as_bytes()).unwrap();
+    let name = CString::new("nested_directory").unwrap();
+    let mut fd = unsafe { libc::open(root.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+    assert!(fd >= 0);
+
+    for _ in 0..depth {
+        assert_eq!(unsafe { libc::mkdirat(fd, name.as_ptr(), 0o755) }, 0);
+        let next = unsafe { libc::openat(fd, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
+        assert!(next >= 0);
+        unsafe { libc::close(fd) };
+        fd = next;
+    }
+
+    unsafe { libc::close(fd) };
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r dir` where dir is 100k levels deep
+fn recursive_remove_deep_directory() {
+    let dir = TempDir::new().unwrap();
+    deep_nested_dir(dir.path(), 100_000);
+    let mut cmd = no_interactive_bin();
+
+    cmd.arg("-r").arg(dir.path()).assert().success();
+    assert!(!dir.path().exists());
+}
+
+/// `sh -c script` as root of a new user and mount namespace, with `$RMX` pointing to the bin.
+/// `None` where user namespaces aren't available
+#[cfg(target_os = "linux")]
+fn in_mount_namespace(script: &str) -> Option<std::process::Command> {
+    use std::process::Command;
+
+    let probe = Command::new("unshare").args(["-rm", "true"]).output();
+    if !probe.is_ok_and(|output| output.status.success()) {
+        eprintln!("skipping, user namespaces are not available");
+        return None;
+    }
+
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+    let mut cmd = Command::new("unshare");
+    cmd.args(["-rm", "sh", "-c", script]).env("RMX", rmx.path());
+    Some(cmd)
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r --one-file-system dir` where dir/mnt is a bind mount of another file system
+fn one_file_system_skips_mount_point() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/file").touch().unwrap();
+    dir.child("tree/nested/file").touch().unwrap();
+    dir.child("tree/mnt").create_dir_all().unwrap();
+    dir.child("other").create_dir_all().unwrap();
+
+    let script = r#"
+        mount -t tmpfs none other && touch other/file && mount --bind other tree/mnt || exit 99
+        "$RMX" -r --one-file-system tree
+        echo "status $?"
+        test -e tree/mnt/file && echo "mount kept"
+    "#;
+    let Some(mut cmd) = in_mount_namespace(script) else {
+        return;
+    };
+
+    let assert = cmd.current_dir(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "rmx: skipping 'tree/mnt', since it's on a different device",
+        ))
+        .stdout(pd::str::contains("status 1"))
+        .stdout(pd::str::contains("mount kept"));
+    dir.child("tree/file").assert(pd::path::missing());
+    dir.child("tree/nested").assert(pd::path::missing());
+    dir.child("tree").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r dir` where dir/mnt is a bind mount of another file system, without --one-file-system
+fn recursive_remove_crosses_mount_point() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/mnt").create_dir_all().unwrap();
+    dir.child("other").create_dir_all().unwrap();
+
+    let script = r#"
+        mount -t tmpfs none other && touch other/file && mount --bind other tree/mnt || exit 99
+        "$RMX" -r tree
+        test -e other/file || echo "mount emptied"
+    "#;
+    let Some(mut cmd) = in_mount_namespace(script) else {
+        return;
+    };
+
+    let assert = cmd.current_dir(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("skipping").count(0))
+        .stdout(pd::str::contains("mount emptied"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r --preserve-root=all mnt` where mnt is a bind mount of another file system
+fn preserve_root_all_skips_mount_point() {
+    let dir = TempDir::new().unwrap();
+    dir.child("mnt").create_dir_all().unwrap();
+    dir.child("other").create_dir_all().unwrap();
+
+    let script = r#"
+        mount -t tmpfs none other && touch other/file && mount --bind other mnt || exit 99
+        "$RMX" -r --preserve-root=all mnt
+        echo "status $?"
+        test -e mnt/file && echo "mount kept"
+        "$RMX" -r --preserve-root mnt/file
+        echo "status $?"
+    "#;
+    let Some(mut cmd) = in_mount_namespace(script) else {
+        return;
+    };
+
+    let assert = cmd.current_dir(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "rmx: skipping 'mnt', since it's on a different device\nrmx: and --preserve-root=all is in effect",
+        ))
+        .stdout(pd::str::contains("status 1\nmount kept\nstatus 0"));
+}
+
+/// `chattr` with `flags` on `path`, `false` where that's not permitted or supported
+#[cfg(target_os = "linux")]
+fn chattr(flags: &str, path: &std::path::Path) -> bool {
+    std::process::Command::new("chattr")
+        .arg(flags)
+        .arg(path)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r --clear-immutable dir` where dir, dir/nested and dir/nested/file are `chattr +i/+a`
+fn recursive_clear_immutable_remove_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/nested/file").touch().unwrap();
+    dir.child("tree/file").touch().unwrap();
+    let tree = dir.path().join("tree");
+    if !chattr("+i", &tree.join("nested/file")) {
+        eprintln!("skipping, setting the immutable attribute is not permitted");
+        return;
+    }
+    chattr("+a", &tree.join("nested"));
+    chattr("+i", &tree);
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-rv").arg("--clear-immutable").arg(&tree).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "cleared immutable and append-only attributes of 'tree/nested/file'",
+        ))
+        .stdout(pd::str::contains(
+            "cleared immutable and append-only attributes of 'tree'",
+        ));
+    dir.child("tree").assert(pd::path::missing());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r --clear-immutable holder/dir` where holder is `chattr +a`, it stays so
+fn recursive_clear_immutable_restores_holder() {
+    let dir = TempDir::new().unwrap();
+    dir.child("holder/tree/file").touch().unwrap();
+    let holder = dir.path().join("holder");
+    if !chattr("+a", &holder) {
+        eprintln!("skipping, setting the append-only attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .arg("-rv")
+        .arg("--clear-immutable")
+        .arg("holder/tree")
+        .assert();
+    let attributes = std::process::Command::new("lsattr")
+        .arg("-d")
+        .arg(&holder)
+        .output()
+        .unwrap();
+    chattr("-a", &holder);
+    assert.success().stdout(pd::str::contains(
+        "cleared immutable and append-only attributes of 'holder'",
+    ));
+    dir.child("holder/tree").assert(pd::path::missing());
+    let attributes = String::from_utf8(attributes.stdout).unwrap();
+    assert!(attributes.split_whitespace().next().unwrap().contains('a'));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -ri --clear-immutable dir` where dir is `chattr +a` and one of its files is kept: dir is
+/// cleared once for all its entries, and gets its attribute back since it stays
+fn recursive_clear_immutable_once_per_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/a").touch().unwrap();
+    dir.child("tree/b").touch().unwrap();
+    dir.child("tree/c").touch().unwrap();
+    let tree = dir.path().join("tree");
+    if !chattr("+a", &tree) {
+        eprintln!("skipping, setting the append-only attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    let assert = cmd
+        .current_dir(dir.path())
+        .arg("-riv")
+        .arg("--clear-immutable")
+        .arg("--answers=y,y,y,n")
+        .arg("tree")
+        .assert();
+    let attributes = std::process::Command::new("lsattr")
+        .arg("-d")
+        .arg(&tree)
+        .output()
+        .unwrap();
+    chattr("-a", &tree);
+    assert.success().stdout(
+        pd::str::contains("cleared immutable and append-only attributes of 'tree'").count(1),
+    );
+    assert_eq!(fs::read_dir(&tree).unwrap().count(), 1);
+    let attributes = String::from_utf8(attributes.stdout).unwrap();
+    assert!(attributes.split_whitespace().next().unwrap().contains('a'));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -r dir` where dir/file is `chattr +i`
+fn recursive_remove_immutable_file_keeps_attribute() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/file").touch().unwrap();
+    let file = dir.path().join("tree/file");
+    if !chattr("+i", &file) {
+        eprintln!("skipping, setting the immutable attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-r").arg(dir.path().join("tree")).assert();
+    chattr("-i", &file);
+    assert
+        .failure()
+        .stdout(pd::str::contains("it is immutable"))
+        .stdout(pd::str::contains("cleared").count(0));
+    dir.child("tree/file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -rf a b` where a/file is `chattr +i`, the error is reported and b removed all the same
+fn recursive_remove_carries_on_past_error() {
+    let dir = TempDir::new().unwrap();
+    dir.child("a/file").touch().unwrap();
+    dir.child("a/other").touch().unwrap();
+    dir.child("b/file").touch().unwrap();
+    let file = dir.path().join("a/file");
+    if !chattr("+i", &file) {
+        eprintln!("skipping, setting the immutable attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.current_dir(dir.path()).args(["-rf", "a", "b"]).assert();
+    chattr("-i", &file);
+    assert
+        .failure()
+        .stdout(pd::str::contains("cannot remove 'a/file'"))
+        .stdout(pd::str::contains("failed to remove").count(0));
+    dir.child("a/file").assert(pd::path::exists());
+    dir.child("a/other").assert(pd::path::missing());
+    dir.child("b").assert(pd::path::missing());
 }
@@ -1,3 +1,6 @@
+// Written before clippy flagged borrowed arrays passed to `args`
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use std::fs;
 
 use assert_cmd::prelude::*;
@@ -6,14 +9,28 @@ use assert_fs::TempDir;
 use escargot::CargoBuild;
 use predicates as pd;
 
-/// Build `rmx` bin that accepts every command line interaction
+/// Build `rmx` bin that answers yes to every prompt
 fn no_interactive_bin() -> std::process::Command {
-    CargoBuild::new()
-        .bin("rmx")
-        .features("auto-interactive")
-        .run()
-        .unwrap()
-        .command()
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+/// Root may write to anything, so GNU `rm` never deems an entry write-protected for it
+fn is_root() -> bool {
+    #[cfg(unix)]
+    return unsafe { libc::geteuid() } == 0;
+    #[cfg(windows)]
+    return false;
+}
+
+/// How prompts describe a read-only entry, depending on who's asking
+fn write_protected() -> &'static str {
+    if is_root() {
+        " "
+    } else {
+        " write-protected "
+    }
 }
 
 #[test]
@@ -53,9 +70,14 @@ fn remove_write_protected_empty_file() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(&filepath).assert();
-    assert.stdout(pd::str::contains(
-        "remove write-protected regular empty file",
-    ));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.child("file").assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains(
+            "remove write-protected regular empty file",
+        ));
+    }
 }
 
 #[test]
@@ -71,7 +93,12 @@ fn remove_write_protected_file() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(&filepath).assert();
-    assert.stdout(pd::str::contains("remove write-protected regular file"));
+    if is_root() {
+        assert.success().stdout(pd::str::is_empty());
+        dir.child("file").assert(pd::path::missing());
+    } else {
+        assert.stdout(pd::str::contains("remove write-protected regular file"));
+    }
 }
 
 #[test]
@@ -111,9 +138,10 @@ fn interactive_remove_write_protected_empty_file() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(&filepath).arg("-i").assert();
-    assert.stdout(pd::str::contains(
-        "remove write-protected regular empty file",
-    ));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}regular empty file",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -129,7 +157,10 @@ fn interactive_remove_write_protected_file() {
 
     let mut cmd = no_interactive_bin();
     let assert = cmd.arg(&filepath).arg("-i").assert();
-    assert.stdout(pd::str::contains("remove write-protected regular file"));
+    assert.stdout(pd::str::contains(format!(
+        "remove{}regular file",
+        write_protected()
+    )));
 }
 
 #[test]
@@ -148,3 +179,172 @@ fn interactive_once_remove_four_empty_files() {
         .assert();
     assert.stdout(pd::str::contains("remove 4 arguments?"));
 }
+
+/// `chattr` with `flags` on `path`, `false` where that's not permitted or supported
+#[cfg(target_os = "linux")]
+fn chattr(flags: &str, path: &std::path::Path) -> bool {
+    std::process::Command::new("chattr")
+        .arg(flags)
+        .arg(path)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm -f file` where file is `chattr +i`
+fn force_remove_immutable_file() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let filepath = dir.path().join("file");
+    if !chattr("+i", &filepath) {
+        eprintln!("skipping, setting the immutable attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-f").arg(&filepath).assert();
+    chattr("-i", &filepath);
+    assert
+        .failure()
+        .stdout(pd::str::contains("it is immutable, 'chattr -i'"));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm -f file` where file is `chattr +a`
+fn force_remove_append_only_file() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let filepath = dir.path().join("file");
+    if !chattr("+a", &filepath) {
+        eprintln!("skipping, setting the append-only attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-f").arg(&filepath).assert();
+    chattr("-a", &filepath);
+    assert
+        .failure()
+        .stdout(pd::str::contains("it is append-only, 'chattr -a'"));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm -f dir/file` where dir is `chattr +i`
+fn force_remove_file_in_immutable_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    let nested = dir.path().join("nested");
+    if !chattr("+i", &nested) {
+        eprintln!("skipping, setting the immutable attribute is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-f").arg(nested.join("file")).assert();
+    chattr("-i", &nested);
+    assert
+        .failure()
+        .stdout(pd::str::contains("the directory holding it is immutable"));
+    dir.child("nested/file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm -f file` as nobody, where file belongs to root in a sticky directory
+fn force_remove_file_in_sticky_directory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if !is_root() {
+        eprintln!("skipping, switching to another user needs root");
+        return;
+    }
+
+    // Somewhere nobody can run the bin from
+    let dir = TempDir::new().unwrap();
+    let rmx = dir.path().join("rmx");
+    fs::copy(no_interactive_bin().get_program(), &rmx).unwrap();
+    dir.child("file").touch().unwrap();
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o1777)).unwrap();
+
+    let mut cmd = std::process::Command::new("setpriv");
+    let assert = cmd
+        .args(["--reuid=65534", "--regid=65534", "--clear-groups"])
+        .arg(&rmx)
+        .arg("-f")
+        .arg(dir.path().join("file"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("the directory holding it is sticky"));
+    dir.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm file </dev/null` as nobody, where file is read-only: like GNU `rm`, nothing is asked
+/// unless stdin is a terminal
+fn remove_write_protected_file_without_terminal() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Stdio;
+
+    if !is_root() {
+        eprintln!("skipping, switching to another user needs root");
+        return;
+    }
+
+    // Somewhere nobody can run the bin from
+    let dir = TempDir::new().unwrap();
+    let rmx = dir.path().join("rmx");
+    fs::copy(CargoBuild::new().bin("rmx").run().unwrap().path(), &rmx).unwrap();
+    dir.child("file").touch().unwrap();
+    fs::set_permissions(dir.path().join("file"), fs::Permissions::from_mode(0o444)).unwrap();
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777)).unwrap();
+
+    let mut cmd = std::process::Command::new("setpriv");
+    let assert = cmd
+        .args(["--reuid=65534", "--regid=65534", "--clear-groups"])
+        .arg(&rmx)
+        .arg(dir.path().join("file"))
+        .stdin(Stdio::null())
+        .assert();
+    assert.success().stdout(pd::str::is_empty());
+    dir.child("file").assert(pd::path::missing());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rm -f --dry-run file` as nobody, where file belongs to root in a sticky directory
+fn dry_run_remove_file_in_sticky_directory() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if !is_root() {
+        eprintln!("skipping, switching to another user needs root");
+        return;
+    }
+
+    // Somewhere nobody can run the bin from
+    let dir = TempDir::new().unwrap();
+    let rmx = dir.path().join("rmx");
+    fs::copy(no_interactive_bin().get_program(), &rmx).unwrap();
+    dir.child("file").touch().unwrap();
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o1777)).unwrap();
+
+    let mut cmd = std::process::Command::new("setpriv");
+    let assert = cmd
+        .args(["--reuid=65534", "--regid=65534", "--clear-groups"])
+        .arg(&rmx)
+        .arg("-f")
+        .arg("--dry-run")
+        .arg(dir.path().join("file"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("the directory holding it is sticky"))
+        .stdout(pd::str::contains("would remove").count(0));
+    dir.child("file").assert(pd::path::exists());
+}
@@ -0,0 +1,450 @@
+#![cfg(unix)]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use escargot::CargoBuild;
+use predicates as pd;
+
+fn bin() -> std::process::Command {
+    CargoBuild::new().bin("rmx").run().unwrap().command()
+}
+
+/// Build `rmx` bin that answers yes to every prompt
+fn no_interactive_bin() -> std::process::Command {
+    let mut cmd = bin();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+const FILES: [&str; 4] = ["a", "b", "c", "d"];
+
+/// Every way of asking for interactivity, or for none
+const FLAGS: [&str; 7] = [
+    "-f",
+    "-i",
+    "-I",
+    "--interactive",
+    "--interactive=never",
+    "--interactive=once",
+    "--interactive=always",
+];
+
+/// What removing four files and a missing one came down to
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    prompts: usize,
+    success: bool,
+    left: Vec<&'static str>,
+}
+
+fn outcome(dir: &TempDir, output: &Output, prompts: &[u8]) -> Outcome {
+    Outcome {
+        prompts: prompts.iter().filter(|&&byte| byte == b'?').count(),
+        success: output.status.success(),
+        left: FILES
+            .into_iter()
+            .filter(|file| dir.path().join(file).exists())
+            .collect(),
+    }
+}
+
+fn setup() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    for file in FILES {
+        dir.child(file).touch().unwrap();
+    }
+    dir
+}
+
+/// `rmx <flags> a b c d missing`
+fn rmx(flags: &[&str]) -> Outcome {
+    let dir = setup();
+    let output = no_interactive_bin()
+        .current_dir(dir.path())
+        .args(flags)
+        .args(FILES)
+        .arg("missing")
+        .output()
+        .unwrap();
+
+    outcome(&dir, &output, &output.stdout)
+}
+
+/// `rm <flags> a b c d missing` answering yes to everything, `None` without GNU `rm` around
+fn gnu_rm(flags: &[&str]) -> Option<Outcome> {
+    let version = Command::new("rm").arg("--version").output().ok()?;
+    if !String::from_utf8_lossy(&version.stdout).contains("GNU coreutils") {
+        return None;
+    }
+
+    let dir = setup();
+    let mut child = Command::new("rm")
+        .current_dir(dir.path())
+        .args(flags)
+        .args(FILES)
+        .arg("missing")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Without prompting `rm` may be gone before reading any of it
+    let _ = child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all("y\n".repeat(FILES.len() + 1).as_bytes());
+    let output = child.wait_with_output().unwrap();
+
+    Some(outcome(&dir, &output, &output.stderr))
+}
+
+#[test]
+/// Every flag on its own and every ordered pair of them, clustered short flags included, behave
+/// like GNU `rm`
+fn interactivity_matches_gnu() {
+    let mut combinations: Vec<Vec<&str>> = vec![vec![]];
+    combinations.extend(FLAGS.iter().map(|&flag| vec![flag]));
+    combinations.extend(
+        FLAGS
+            .iter()
+            .flat_map(|&first| FLAGS.iter().map(move |&second| vec![first, second])),
+    );
+    combinations.extend(["-fi", "-if", "-fI", "-If", "-iI", "-Ii"].map(|flags| vec![flags]));
+
+    for flags in combinations {
+        let Some(expected) = gnu_rm(&flags) else {
+            eprintln!("skipping, GNU rm is not available");
+            return;
+        };
+
+        assert_eq!(rmx(&flags), expected, "rmx {}", flags.join(" "));
+    }
+}
+
+#[test]
+/// `rmx -if a b c d missing`
+fn interactive_then_force_remove_files() {
+    assert_eq!(
+        rmx(&["-if"]),
+        Outcome {
+            prompts: 0,
+            success: true,
+            left: vec![],
+        }
+    );
+}
+
+#[test]
+/// `rmx -fi a b c d missing`
+fn force_then_interactive_remove_files() {
+    assert_eq!(
+        rmx(&["-fi"]),
+        Outcome {
+            prompts: 4,
+            success: false,
+            left: vec![],
+        }
+    );
+}
+
+#[test]
+/// `rmx -f --interactive=never a b c d missing`, never prompting keeps ignoring missing files
+fn force_then_interactive_never_remove_files() {
+    assert_eq!(
+        rmx(&["-f", "--interactive=never"]),
+        Outcome {
+            prompts: 0,
+            success: true,
+            left: vec![],
+        }
+    );
+}
+
+#[test]
+/// `rmx missing`
+fn remove_missing_file() {
+    let dir = TempDir::new().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.current_dir(dir.path()).arg("missing").assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: cannot remove 'missing': No such file or directory",
+    ));
+}
+
+#[test]
+/// `rmx -i -f`, nothing to do without operands once missing files are ignored
+fn interactive_then_force_without_operands() {
+    let mut cmd = no_interactive_bin();
+    cmd.args(["-i", "-f"]).assert().success();
+
+    let mut cmd = no_interactive_bin();
+    cmd.args(["-f", "-i"]).assert().failure();
+}
+
+#[test]
+/// `rmx --assume-no -i a b c d missing`
+fn assume_no_interactive_remove_files() {
+    let dir = setup();
+
+    let mut cmd = bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["--assume-no", "-i"])
+        .args(FILES)
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: remove regular empty file 'a'? n"))
+        .stdout(pd::str::contains("rmx: remove regular empty file 'd'? n"));
+    for file in FILES {
+        dir.child(file).assert(pd::path::exists());
+    }
+}
+
+#[test]
+/// `rmx --assume-yes --assume-no file`
+fn assume_yes_conflicts_with_assume_no() {
+    let dir = setup();
+
+    let mut cmd = bin();
+    cmd.current_dir(dir.path())
+        .args(["--assume-yes", "--assume-no", "a"])
+        .assert()
+        .failure();
+    dir.child("a").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx -i file` in a new session, without a terminal to ask on
+fn interactive_remove_file_without_terminal() {
+    let dir = setup();
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+
+    let mut cmd = Command::new("setsid");
+    let assert = cmd
+        .current_dir(dir.path())
+        .arg("--wait")
+        .arg(rmx.path())
+        .args(["-i", "a"])
+        .stdin(Stdio::null())
+        .assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: cannot prompt without a terminal, answer with --assume-yes or --assume-no",
+    ));
+    dir.child("a").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -i a` on a terminal answered with a bare enter, which is a no like with GNU `rm`
+fn interactive_remove_file_enter_declines() {
+    let dir = setup();
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+
+    // `script` runs the command on a terminal of its own, fed from its stdin
+    let Ok(mut script) = Command::new("script")
+        .current_dir(dir.path())
+        .args([
+            "-qec",
+            &format!("{} -i a", rmx.path().display()),
+            "/dev/null",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    else {
+        return;
+    };
+    let _ = script.stdin.take().unwrap().write_all(b"\n");
+    let output = script.wait_with_output().unwrap();
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("[q]uit [n]: "));
+    dir.child("a").assert(pd::path::exists());
+}
+
+/// Build `rmx` bin that answers prompts from the comma separated `answers`
+fn scripted_bin(answers: &str) -> std::process::Command {
+    let mut cmd = bin();
+    cmd.arg(format!("--answers={answers}"));
+    cmd
+}
+
+/// `a b c d` along with `tree/{x,y,z}`
+fn setup_tree() -> TempDir {
+    let dir = setup();
+    for file in ["x", "y", "z"] {
+        dir.child("tree").child(file).touch().unwrap();
+    }
+    dir
+}
+
+fn prompts(output: &[u8]) -> usize {
+    output.iter().filter(|&&byte| byte == b'?').count()
+}
+
+#[test]
+/// `rmx -i a b`, declining a
+fn answers_decline_file() {
+    let dir = setup();
+
+    let mut cmd = scripted_bin("n,y");
+    let assert = cmd.current_dir(dir.path()).args(["-i", "a", "b"]).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: remove regular empty file 'a'? n"))
+        .stdout(pd::str::contains("rmx: remove regular empty file 'b'? y"));
+    dir.child("a").assert(pd::path::exists());
+    dir.child("b").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -ri tree`, skipping the rest of tree at its first entry
+fn answers_skip_rest_of_directory() {
+    let dir = setup_tree();
+
+    let mut cmd = scripted_bin("y,s");
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-r", "-i", "tree"])
+        .assert();
+    let output = assert.success().get_output().stdout.clone();
+    assert_eq!(prompts(&output), 2);
+    for file in ["x", "y", "z"] {
+        dir.child("tree").child(file).assert(pd::path::exists());
+    }
+}
+
+#[test]
+/// `rmx -ri tree a`, skipping tree altogether
+fn answers_skip_directory() {
+    let dir = setup_tree();
+
+    let mut cmd = scripted_bin("s,y");
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-r", "-i", "tree", "a"])
+        .assert();
+    assert.success().stdout(pd::str::contains(
+        "rmx: descend into directory 'tree' (3 files, 0 B)? s",
+    ));
+    dir.child("tree/x").assert(pd::path::exists());
+    dir.child("a").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -ri tree a`, answering all at the first entry of tree
+fn answers_all() {
+    let dir = setup_tree();
+
+    let mut cmd = scripted_bin("y,a");
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-r", "-i", "tree", "a"])
+        .assert();
+    let output = assert.success().get_output().stdout.clone();
+    assert_eq!(prompts(&output), 2);
+    dir.child("tree").assert(pd::path::missing());
+    dir.child("a").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -i a b c d`, quitting at b
+fn answers_quit() {
+    let dir = setup();
+
+    let mut cmd = scripted_bin("y,q");
+    let assert = cmd.current_dir(dir.path()).arg("-i").args(FILES).assert();
+    let output = assert.success().get_output().stdout.clone();
+    assert_eq!(prompts(&output), 2);
+    dir.child("a").assert(pd::path::missing());
+    for file in ["b", "c", "d"] {
+        dir.child(file).assert(pd::path::exists());
+    }
+}
+
+#[test]
+/// `rmx -i a b` with a single answer
+fn answers_run_out() {
+    let dir = setup();
+
+    let mut cmd = scripted_bin("y");
+    let assert = cmd.current_dir(dir.path()).args(["-i", "a", "b"]).assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: ran out of answers given with --answers or RMX_ANSWERS",
+    ));
+    dir.child("a").assert(pd::path::missing());
+    dir.child("b").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -i a` answering maybe
+fn answers_invalid() {
+    let dir = setup();
+
+    let mut cmd = scripted_bin("maybe");
+    let assert = cmd.current_dir(dir.path()).args(["-i", "a"]).assert();
+    assert.failure().stdout(pd::str::contains(
+        "rmx: invalid answer 'maybe' given with --answers or RMX_ANSWERS",
+    ));
+    dir.child("a").assert(pd::path::exists());
+}
+
+#[test]
+/// `RMX_ANSWERS=n rmx -i a`
+fn answers_from_environment() {
+    let dir = setup();
+
+    let mut cmd = bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .env("RMX_ANSWERS", "n")
+        .args(["-i", "a"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: remove regular empty file 'a'? n"));
+    dir.child("a").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -rI tree`, declining the upfront prompt
+fn answers_decline_interactive_once() {
+    let dir = setup_tree();
+
+    let mut cmd = scripted_bin("n");
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-r", "-I", "tree"])
+        .assert();
+    assert.success().stdout(pd::str::contains(
+        "rmx: remove 1 argument recursively (3 files, 0 B)? n",
+    ));
+    dir.child("tree/x").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx -ri --summary tree`, declining x and skipping the rest of tree at y
+fn answers_summary_counts_declined() {
+    let dir = setup_tree();
+
+    let mut cmd = scripted_bin("y,n,s");
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["-r", "-i", "--summary", "tree"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "removed: 0 files, 0 directories, 0 symlinks",
+        ))
+        .stdout(pd::str::contains(
+            "skipped: 2 (0 preserve-root, 0 other device, 2 declined, 0 filtered)",
+        ));
+    dir.child("tree/x").assert(pd::path::exists());
+}
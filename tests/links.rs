@@ -3,15 +3,13 @@ use assert_fs::prelude::*;
 use assert_fs::TempDir;
 use escargot::CargoBuild;
 use predicates as pd;
+use predicates::prelude::PredicateBooleanExt;
 
-/// Build `rmx` bin that accepts every command line interaction
+/// Build `rmx` bin that answers yes to every prompt
 fn no_interactive_bin() -> std::process::Command {
-    CargoBuild::new()
-        .bin("rmx")
-        .features("auto-interactive")
-        .run()
-        .unwrap()
-        .command()
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
 }
 
 #[cfg(unix)]
@@ -44,3 +42,133 @@ fn interactive_follow_symlink_remove_symlink() {
     let assert = cmd.arg("-i").arg("-l").arg(&link).assert();
     assert.stdout(pd::str::contains("remove regular empty file"));
 }
+
+#[test]
+#[cfg(unix)]
+/// `rmx -l link` where link points to itself
+fn follow_symlink_remove_self_loop() {
+    let dir = TempDir::new().unwrap();
+    let link = dir.path().join("link");
+    symlink(&link, &link).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-l").arg(&link).assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("not following symbolic link loop"))
+        .stdout(pd::str::contains("cannot remove").count(0));
+    assert!(link.symlink_metadata().is_err());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -l a` where a -> b -> a
+fn follow_symlink_remove_loop() {
+    let dir = TempDir::new().unwrap();
+    let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+    symlink(&b, &a).unwrap();
+    symlink(&a, &b).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-l").arg(&a).assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("not following symbolic link loop"))
+        .stdout(pd::str::contains("cannot remove").count(0));
+    assert!(a.symlink_metadata().is_err());
+    assert!(b.symlink_metadata().is_err());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -rl dir` where dir/nested/link -> ..
+fn follow_symlink_remove_link_to_ancestor() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").touch().unwrap();
+    symlink("..", dir.path().join("nested/link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-rl").arg(dir.path()).assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("circular directory structure"));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -lv dir/link` where link -> file, run from another directory
+fn follow_symlink_resolves_relative_target() {
+    let dir = TempDir::new().unwrap();
+    let cwd = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let link = dir.path().join("link");
+    symlink("file", &link).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.current_dir(cwd.path()).arg("-lv").arg(&link).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("removed 'link -> file'"));
+    dir.child("file").assert(pd::path::missing());
+    assert!(link.symlink_metadata().is_err());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r --follow-links=inside dir` where dir/link points outside of dir
+fn follow_symlink_inside_keeps_outside_target() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    other.child("file").touch().unwrap();
+    symlink(other.path().join("file"), dir.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--follow-links=inside")
+        .arg(dir.path())
+        .assert();
+    assert.success().stdout(pd::str::contains("not following"));
+    assert!(!dir.path().exists());
+    other.child("file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -r --follow-links=inside dir` where dir/nested/link -> ../file
+fn follow_symlink_inside_removes_inside_target() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    dir.child("nested").create_dir_all().unwrap();
+    symlink("../file", dir.path().join("nested/link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-rv")
+        .arg("--follow-links=inside")
+        .arg(dir.path())
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("not following").not())
+        .stdout(pd::str::contains("No such file").not())
+        .stdout(pd::str::contains("/nested/link -> ../file'"));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx -rl dir` where dir/link points outside of dir
+fn follow_symlink_any_removes_outside_target() {
+    let dir = TempDir::new().unwrap();
+    let other = TempDir::new().unwrap();
+    other.child("file").touch().unwrap();
+    symlink(other.path().join("file"), dir.path().join("link")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-rl").arg(dir.path()).assert();
+    assert.success();
+    assert!(!dir.path().exists());
+    other.child("file").assert(pd::path::missing());
+}
@@ -0,0 +1,109 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use escargot::CargoBuild;
+use predicates as pd;
+
+/// Build `rmx` bin that answers yes to every prompt, picking everything
+fn no_interactive_bin() -> std::process::Command {
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+#[test]
+/// `rmx --pick dir file`
+fn pick_remove_directory_and_file() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/nested/file")
+        .write_binary(&[0; 1536])
+        .unwrap();
+    dir.child("tree/empty").touch().unwrap();
+    dir.child("file").write_str("hello").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["--pick", "tree", "file"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: pick what to remove"))
+        .stdout(pd::str::contains("tree/ (1.5 KiB)"))
+        .stdout(pd::str::contains("  empty (0 B)"))
+        .stdout(pd::str::contains("  nested/ (1.5 KiB)"))
+        .stdout(pd::str::contains("    file (1.5 KiB)"))
+        .stdout(pd::str::contains("file (5 B)"));
+    dir.child("tree").assert(pd::path::missing());
+    dir.child("file").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx --pick dir` lists three levels below the operand, deeper entries count towards sizes
+fn pick_lists_three_levels() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/one/two/three/four/file")
+        .write_binary(&[0; 2048])
+        .unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["--pick", "tree"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("      three/ (2.0 KiB)"))
+        .stdout(pd::str::contains("four").count(0));
+    dir.child("tree").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx --pick dir`, checking a single file below dir
+fn pick_remove_checked_file() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/a").touch().unwrap();
+    dir.child("tree/b").touch().unwrap();
+
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["--pick", "--answers=1", "tree"])
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("\n1\n"))
+        .stdout(pd::str::contains(" 1\n").count(0));
+    dir.child("tree/a").assert(pd::path::missing());
+    dir.child("tree/b").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --pick missing`
+fn pick_remove_missing_file() {
+    let dir = TempDir::new().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .current_dir(dir.path())
+        .args(["--pick", "missing"])
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("rmx: pick what to remove").count(0))
+        .stdout(pd::str::contains("cannot remove 'missing'"));
+}
+
+#[test]
+/// `rmx --pick --rip dir`
+fn pick_conflicts_with_rip() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/file").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    cmd.current_dir(dir.path())
+        .args(["--pick", "--rip", "tree"])
+        .assert()
+        .failure();
+    dir.child("tree/file").assert(pd::path::exists());
+}
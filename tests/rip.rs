@@ -0,0 +1,308 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use escargot::CargoBuild;
+use predicates as pd;
+
+/// Build `rmx` bin that answers yes to every prompt
+fn no_interactive_bin() -> std::process::Command {
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+fn nested_dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    dir.child("a/b/c").create_dir_all().unwrap();
+    dir.child("a/file").touch().unwrap();
+    dir.child("a/b/c/file").touch().unwrap();
+
+    dir
+}
+
+#[test]
+/// `rmx --rip dir`
+fn rip_remove_directory() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg(dir.path()).assert();
+    assert.stdout(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx --rip -v dir`
+fn rip_verbose_remove_directory() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg("-v").arg(dir.path()).assert();
+    assert
+        .stdout(pd::str::contains("removed '"))
+        .stdout(pd::str::contains("directory '"));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx --rip --dry-run dir`
+fn rip_dry_run_remove_directory() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg("--dry-run").arg(dir.path()).assert();
+    assert
+        .stdout(pd::str::contains("would remove '"))
+        .stdout(pd::str::contains("would remove directory '"));
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip --protect dir dir`
+fn rip_protect_dir_remove_directory() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .arg("--protect")
+        .arg(dir.path())
+        .arg(dir.path())
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"));
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip --protect dir dir/a`
+fn rip_protect_dir_remove_nested_directory() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .arg("--protect")
+        .arg(dir.path())
+        .arg(dir.path().join("a"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("refusing to remove"));
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+/// `sh -c script` as root of a new user and mount namespace, with `$RMX` pointing to the bin.
+/// `None` where user namespaces aren't available
+#[cfg(target_os = "linux")]
+fn in_mount_namespace(script: &str) -> Option<std::process::Command> {
+    use std::process::Command;
+
+    let probe = Command::new("unshare").args(["-rm", "true"]).output();
+    if !probe.is_ok_and(|output| output.status.success()) {
+        eprintln!("skipping, user namespaces are not available");
+        return None;
+    }
+
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+    let mut cmd = Command::new("unshare");
+    cmd.args(["-rm", "sh", "-c", script]).env("RMX", rmx.path());
+    Some(cmd)
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx --rip --one-file-system dir` where dir/mnt is a bind mount of another file system
+fn rip_one_file_system_skips_mount_point() {
+    let dir = TempDir::new().unwrap();
+    dir.child("tree/file").touch().unwrap();
+    dir.child("tree/nested/file").touch().unwrap();
+    dir.child("tree/mnt").create_dir_all().unwrap();
+    dir.child("other").create_dir_all().unwrap();
+
+    let script = r#"
+        mount -t tmpfs none other && touch other/file && mount --bind other tree/mnt || exit 99
+        "$RMX" --rip --one-file-system tree
+        echo "status $?"
+        test -e tree/mnt/file && echo "mount kept"
+    "#;
+    let Some(mut cmd) = in_mount_namespace(script) else {
+        return;
+    };
+
+    let assert = cmd.current_dir(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "rmx: skipping 'tree/mnt', since it's on a different device",
+        ))
+        .stdout(pd::str::contains("status 1"))
+        .stdout(pd::str::contains("mount kept"));
+    dir.child("tree/file").assert(pd::path::missing());
+    dir.child("tree/nested").assert(pd::path::missing());
+    dir.child("tree").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip missing`
+fn rip_remove_missing_path() {
+    let dir = TempDir::new().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg(dir.path().join("missing")).assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("No such file or directory"))
+        .stdout(pd::str::contains("failed to remove 1 entry"));
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx --rip link`
+fn rip_remove_symlink_to_directory() {
+    let dir = nested_dir();
+    let other = TempDir::new().unwrap();
+    let link = other.path().join("link");
+    std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg(&link).assert();
+    assert.success().stdout(pd::str::is_empty());
+    assert!(link.symlink_metadata().is_err());
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx --rip dir` where dir holds names that aren't valid UTF-8
+fn rip_remove_non_utf8_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = nested_dir();
+    let name = OsStr::from_bytes(b"\xff\xfe");
+    std::fs::create_dir(dir.path().join("a").join(name)).unwrap();
+    std::fs::write(dir.path().join("a").join(name).join(name), b"").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg(dir.path()).assert();
+    assert.success().stdout(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx --rip --threads 1 dir`
+fn rip_single_thread_remove_wide_directory() {
+    let dir = nested_dir();
+    for i in 0..500 {
+        dir.child(format!("a/b/file{i}")).touch().unwrap();
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .args(["--threads", "1"])
+        .arg(dir.path())
+        .assert();
+    assert.success().stdout(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx --rip --threads 0 dir`
+fn rip_zero_threads_is_rejected() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .args(["--threads", "0"])
+        .arg(dir.path())
+        .assert();
+    assert.failure();
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip --progress dir` with stderr redirected, nothing is drawn
+fn rip_progress_without_terminal() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--rip").arg("--progress").arg(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::is_empty())
+        .stderr(pd::str::is_empty());
+    assert!(!dir.path().exists());
+}
+
+#[test]
+#[cfg(unix)]
+/// `rmx --rip --progress dir` on a terminal, the status line is cleared once done
+fn rip_progress_on_terminal() {
+    let dir = nested_dir();
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+    let command = format!(
+        "{} --rip --progress {}",
+        rmx.path().display(),
+        dir.path().display()
+    );
+
+    // `script` runs the command on a terminal of its own
+    let Ok(output) = std::process::Command::new("script")
+        .args(["-qec", &command, "/dev/null"])
+        .stdin(std::process::Stdio::null())
+        .output()
+    else {
+        return;
+    };
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\r\x1b[2K"));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx --rip --progress -v dir`
+fn rip_progress_conflicts_with_verbose() {
+    let dir = nested_dir();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .arg("--progress")
+        .arg("-v")
+        .arg(dir.path())
+        .assert();
+    assert.failure();
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
+
+#[test]
+/// `rmx --rip --summary --protect dir/a/b/c dir`
+fn rip_summary_counts_filtered() {
+    let dir = nested_dir();
+    dir.child("a/file").write_str("hello").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--rip")
+        .arg("--summary")
+        .arg("--protect")
+        .arg(dir.path().join("a/b/c"))
+        .arg(dir.path())
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains(
+            "removed: 1 file, 0 directories, 0 symlinks",
+        ))
+        .stdout(pd::str::contains("freed: 5 B"))
+        .stdout(pd::str::contains(
+            "skipped: 1 (0 preserve-root, 0 other device, 0 declined, 1 filtered)",
+        ))
+        .stdout(pd::str::contains("errors: 0"))
+        .stdout(pd::str::contains("elapsed: "));
+    dir.child("a/b/c/file").assert(pd::path::exists());
+}
@@ -5,15 +5,13 @@ use assert_fs::prelude::*;
 use assert_fs::TempDir;
 use escargot::CargoBuild;
 use predicates as pd;
+use predicates::prelude::PredicateBooleanExt;
 
-/// Build `rmx` bin that accepts every command line interaction
+/// Build `rmx` bin that answers yes to every prompt
 fn no_interactive_bin() -> std::process::Command {
-    CargoBuild::new()
-        .bin("rmx")
-        .features("auto-interactive")
-        .run()
-        .unwrap()
-        .command()
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
 }
 
 #[test]
@@ -56,3 +54,312 @@ fn shred_doesnt_leave_artifacts() {
 
     assert!(leftovers.next().is_none());
 }
+
+#[test]
+fn wipe_free_space_missing_directory() {
+    let dir = TempDir::new().unwrap();
+    let missing = dir.path().join("missing");
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--wipe-free-space").arg(&missing).assert();
+    assert.stdout(pd::str::contains("No such file or directory"));
+}
+
+#[test]
+fn wipe_free_space_not_a_directory() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let filepath = dir.path().join("file");
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--wipe-free-space").arg(&filepath).assert();
+    assert.stdout(pd::str::contains("Not a directory"));
+    assert!(filepath.exists());
+}
+
+#[test]
+/// The operands are removed before the free space is wiped
+fn wipe_free_space_after_removal() {
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    dir.child("not_a_dir").touch().unwrap();
+    let filepath = dir.path().join("file");
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--wipe-free-space")
+        .arg(dir.path().join("not_a_dir"))
+        .arg(&filepath)
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("Not a directory"));
+    assert!(!filepath.exists());
+}
+
+#[test]
+/// Nothing is wiped when an operand couldn't be removed
+fn wipe_free_space_skipped_when_removal_fails() {
+    let dir = TempDir::new().unwrap();
+    dir.child("not_a_dir").touch().unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("--wipe-free-space")
+        .arg(dir.path().join("not_a_dir"))
+        .arg(dir.path().join("missing"))
+        .assert();
+    assert
+        .failure()
+        .stdout(pd::str::contains("No such file or directory"))
+        .stdout(pd::str::contains("Not a directory").not());
+}
+
+#[test]
+#[cfg(unix)]
+/// Metadata lives in the inode, so a second hard link shows what was left behind
+fn shred_scrubs_metadata() {
+    use std::time::UNIX_EPOCH;
+
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let filepath = dir.path().join("file");
+    let witness = dir.path().join("witness");
+    fs::write(&filepath, b"foo bar baz").unwrap();
+    fs::hard_link(&filepath, &witness).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--shred").arg(&filepath).assert();
+    assert.stdout(pd::str::is_empty());
+
+    let metadata = fs::metadata(&witness).unwrap();
+    assert!(!filepath.exists());
+    assert_eq!(metadata.len(), 0);
+    assert_eq!(metadata.modified().unwrap(), UNIX_EPOCH);
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn shred_strips_extended_attributes() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = TempDir::new().unwrap();
+    dir.child("file").touch().unwrap();
+    let filepath = dir.path().join("file");
+    let witness = dir.path().join("witness");
+    fs::write(&filepath, b"foo bar baz").unwrap();
+    fs::hard_link(&filepath, &witness).unwrap();
+
+    let path = CString::new(filepath.as_os_str().as_bytes()).unwrap();
+    let value = b"Matthew McConaughey";
+    let set = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            c"user.rmx".as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if set == -1 {
+        // File system without user xattrs
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--shred").arg(&filepath).assert();
+    assert.stdout(pd::str::is_empty());
+
+    let witness = CString::new(witness.as_os_str().as_bytes()).unwrap();
+    let size = unsafe { libc::listxattr(witness.as_ptr(), std::ptr::null_mut(), 0) };
+    assert!(size <= 0);
+}
+
+#[test]
+/// `rmx -r --shred --summary dir`
+fn shred_summary_counts_removed() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested/file").write_str("foo bar baz").unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd
+        .arg("-r")
+        .arg("--shred")
+        .arg("--summary")
+        .arg(dir.path())
+        .assert();
+    assert
+        .success()
+        .stdout(pd::str::contains(
+            "removed: 1 file, 2 directories, 0 symlinks",
+        ))
+        .stdout(pd::str::contains("freed: 11 B"))
+        .stdout(pd::str::contains("errors: 0"));
+    assert!(!dir.path().exists());
+}
+
+/// Block special file at `path` for a device that's unlikely to exist, `false` without the
+/// privileges to create one
+#[cfg(target_os = "linux")]
+fn mknod_block(path: &std::path::Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let dev = libc::makedev(240, 0);
+
+    unsafe { libc::mknod(path.as_ptr(), libc::S_IFBLK | 0o644, dev) == 0 }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx --assume-yes --shred blk`, only the device name typed on the terminal shreds it
+fn shred_block_device_refuses_assumed_yes() {
+    let dir = TempDir::new().unwrap();
+    let device = dir.path().join("blk");
+    if !mknod_block(&device) {
+        eprintln!("skipping, creating a block device needs privileges");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--shred").arg(&device).assert();
+    assert.stdout(pd::str::contains(
+        "all data on the device will be lost n, only typing it out on a terminal says yes",
+    ));
+    dir.child("blk").assert(pd::path::exists());
+}
+
+/// A loop device backed by `image`, detached when dropped
+#[cfg(target_os = "linux")]
+struct LoopDevice(String);
+
+#[cfg(target_os = "linux")]
+impl LoopDevice {
+    /// `None` without the privileges to set one up
+    fn attach(image: &std::path::Path) -> Option<Self> {
+        let output = std::process::Command::new("losetup")
+            .arg("--find")
+            .arg("--show")
+            .arg(image)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+
+        Some(Self(
+            String::from_utf8(output.stdout).ok()?.trim().to_owned(),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for LoopDevice {
+    fn drop(&mut self) {
+        let _ = std::process::Command::new("losetup")
+            .arg("-d")
+            .arg(&self.0)
+            .status();
+    }
+}
+
+/// `rmx --shred device` on a pseudo terminal, with `typed` as what's typed on it
+#[cfg(target_os = "linux")]
+fn shred_on_terminal(device: &str, typed: &str) -> assert_cmd::assert::Assert {
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+    let mut cmd = std::process::Command::new("script");
+    cmd.arg("-qec")
+        .arg(format!("'{}' --shred '{device}'", rmx.path().display()))
+        .arg("/dev/null");
+
+    assert_cmd::Command::from_std(cmd)
+        .write_stdin(format!("{typed}\n"))
+        .assert()
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// Anything but the device name typed on the terminal leaves it alone
+fn shred_block_device_declined() {
+    let dir = TempDir::new().unwrap();
+    let image = dir.path().join("image");
+    fs::write(&image, vec![0_u8; 64 * 1024]).unwrap();
+    let Some(device) = LoopDevice::attach(&image) else {
+        eprintln!("skipping, setting up a loop device needs privileges");
+        return;
+    };
+
+    let name = device.0.trim_start_matches("/dev/");
+    let assert = shred_on_terminal(&device.0, "yes");
+    assert.stdout(pd::str::contains(format!("type '{name}' to go ahead")));
+    assert!(std::path::Path::new(&device.0).exists());
+    drop(device);
+
+    assert!(fs::read(&image).unwrap().iter().all(|&byte| byte == 0));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// Typing the device name overwrites it and keeps the node
+fn shred_block_device_accepted() {
+    let dir = TempDir::new().unwrap();
+    let image = dir.path().join("image");
+    fs::write(&image, vec![0_u8; 64 * 1024]).unwrap();
+    let Some(device) = LoopDevice::attach(&image) else {
+        eprintln!("skipping, setting up a loop device needs privileges");
+        return;
+    };
+
+    let assert = shred_on_terminal(&device.0, device.0.trim_start_matches("/dev/"));
+    assert.stdout(pd::str::contains("cannot").count(0));
+    assert!(std::path::Path::new(&device.0).exists());
+    drop(device);
+
+    let shredded = fs::read(&image).unwrap();
+    assert_eq!(shredded.len(), 64 * 1024);
+    assert!(shredded.iter().any(|&byte| byte != 0));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+/// `rmx --wipe-free-space fs` on a small tmpfs, filled up and emptied again
+fn wipe_free_space_fills_and_releases() {
+    use std::process::Command;
+
+    let probe = Command::new("unshare").args(["-rm", "true"]).output();
+    if !probe.is_ok_and(|output| output.status.success()) {
+        eprintln!("skipping, user namespaces are not available");
+        return;
+    }
+
+    let dir = TempDir::new().unwrap();
+    dir.child("fs").create_dir_all().unwrap();
+    let script = r#"
+        mount -t tmpfs -o size=1m none fs && echo kept > fs/kept || exit 99
+        "$RMX" --verbose --wipe-free-space fs
+        echo "status $?"
+        df --output=used -B1 fs | tail -n 1 | xargs echo used
+        ls -A fs
+    "#;
+
+    let rmx = CargoBuild::new().bin("rmx").run().unwrap();
+    let mut cmd = Command::new("unshare");
+    cmd.args(["-rm", "sh", "-c", script])
+        .env("RMX", rmx.path())
+        .current_dir(dir.path());
+
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    let wiped: u64 = output
+        .split_whitespace()
+        .skip_while(|&word| word != "wiped")
+        .nth(1)
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap();
+
+    assert!(wiped > 512 * 1024, "{output}");
+    assert!(output.contains("status 0"), "{output}");
+    assert!(output.contains("used 4096"), "{output}");
+    assert!(output.trim_end().ends_with("kept"), "{output}");
+}
@@ -0,0 +1,118 @@
+#![cfg(unix)]
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use assert_fs::TempDir;
+use escargot::CargoBuild;
+use predicates as pd;
+
+/// Build `rmx` bin that answers yes to every prompt
+fn no_interactive_bin() -> std::process::Command {
+    let mut cmd = CargoBuild::new().bin("rmx").run().unwrap().command();
+    cmd.arg("--assume-yes");
+    cmd
+}
+
+fn mkfifo(path: &Path) {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+}
+
+/// `/dev/null` lookalike at `path`, `false` without the privileges to create one
+fn mknod_null(path: &Path) -> bool {
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let dev = libc::makedev(1, 3);
+
+    unsafe { libc::mknod(path.as_ptr(), libc::S_IFCHR | 0o644, dev) == 0 }
+}
+
+#[test]
+/// `rmx fifo`
+fn remove_fifo() {
+    let dir = TempDir::new().unwrap();
+    mkfifo(&dir.path().join("fifo"));
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg(dir.path().join("fifo")).assert();
+    assert.success().stdout(pd::str::is_empty());
+    dir.child("fifo").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -i fifo`
+fn interactive_remove_fifo() {
+    let dir = TempDir::new().unwrap();
+    mkfifo(&dir.path().join("fifo"));
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-i").arg(dir.path().join("fifo")).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: remove fifo 'fifo'?"));
+    dir.child("fifo").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx --shred fifo`
+fn shred_remove_fifo() {
+    let dir = TempDir::new().unwrap();
+    mkfifo(&dir.path().join("fifo"));
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("--shred").arg(dir.path().join("fifo")).assert();
+    assert.success();
+    dir.child("fifo").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -i socket`
+fn interactive_remove_socket() {
+    let dir = TempDir::new().unwrap();
+    let _listener = UnixListener::bind(dir.path().join("socket")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-i").arg(dir.path().join("socket")).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("rmx: remove socket 'socket'?"));
+    dir.child("socket").assert(pd::path::missing());
+}
+
+#[test]
+/// `rmx -ri dir` where dir holds a fifo and a socket
+fn recursive_interactive_remove_special_files() {
+    let dir = TempDir::new().unwrap();
+    dir.child("nested").create_dir_all().unwrap();
+    mkfifo(&dir.path().join("nested/fifo"));
+    let _listener = UnixListener::bind(dir.path().join("nested/socket")).unwrap();
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-ri").arg(dir.path()).assert();
+    assert
+        .success()
+        .stdout(pd::str::contains("remove fifo"))
+        .stdout(pd::str::contains("remove socket"));
+    assert!(!dir.path().exists());
+}
+
+#[test]
+/// `rmx -i null` where null is a character device
+fn interactive_remove_char_device() {
+    let dir = TempDir::new().unwrap();
+    if !mknod_null(&dir.path().join("null")) {
+        eprintln!("skipping, creating device nodes is not permitted");
+        return;
+    }
+
+    let mut cmd = no_interactive_bin();
+    let assert = cmd.arg("-i").arg(dir.path().join("null")).assert();
+    assert.success().stdout(pd::str::contains(
+        "rmx: remove character special file 'null'?",
+    ));
+    dir.child("null").assert(pd::path::missing());
+}